@@ -0,0 +1,205 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+};
+
+use filetime::FileTime;
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    data::{HashType, Hashes, HASH_ORDER},
+};
+
+/// Downloaded archives, keyed by the verified hash from hashes.json.
+pub struct Cache {
+    path: PathBuf,
+    size: u64, // Bytes, 0 is unlimited
+}
+impl Cache {
+    pub fn new(config: &Config) -> Option<Self> {
+        if config.no_cache {
+            return None;
+        }
+
+        Some(Self {
+            path: config.cache_path.clone(),
+            size: config.cache_size.saturating_mul(1024 * 1024),
+        })
+    }
+
+    pub fn get(&self, hashes: &Hashes) -> Option<Vec<u8>> {
+        let (hash_type, hash) = key(hashes)?;
+        let path = self.path.join(file_name(hash_type, hash));
+        let bytes = fs::read(&path).ok()?;
+
+        // Never trust the cache, a bad file is removed and downloaded again.
+        if hash_type.digest(&bytes).eq_ignore_ascii_case(hash) {
+            // Pruning goes by mtime, so a hit keeps the archive around.
+            let _ = filetime::set_file_mtime(&path, FileTime::now());
+            Some(bytes)
+        }
+        else {
+            eprintln!(
+                "{} cached archive {path:?}, removing it.",
+                err_color_print("Corrupted", PossibleColor::BrightYellow)
+            );
+            let _ = fs::remove_file(&path);
+            None
+        }
+    }
+
+    pub fn put(&self, hashes: &Hashes, bytes: &[u8]) {
+        let Some((hash_type, hash)) = key(hashes)
+        else {
+            return;
+        };
+
+        if create_dir_all(&self.path).is_err() {
+            eprintln!("Could not create the cache directory {:?}.", self.path);
+            return;
+        }
+
+        // Written to a temp file first, so a partial write is never used.
+        let path = self.path.join(file_name(hash_type, hash));
+        let tmp = path.with_extension("part");
+        if fs::write(&tmp, bytes)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .is_err()
+        {
+            let _ = fs::remove_file(&tmp);
+            eprintln!("Could not add {path:?} to the cache.");
+            return;
+        }
+
+        self.prune();
+    }
+
+    /// Remove the oldest archives until the cache fits in its size limit.
+    fn prune(&self) {
+        if self.size == 0 {
+            return;
+        }
+
+        let mut files = entries(&self.path);
+        files.sort_by_key(|(_, _, modified)| std::cmp::Reverse(*modified));
+
+        let mut total = 0;
+        for (path, len, _) in files {
+            total += len;
+            if total > self.size {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Remove every archive and all index metadata from the cache.
+pub fn clean(config: &Config) {
+    let mut count = 0;
+    let mut total = 0;
+    for (path, len, _) in entries(&config.cache_path) {
+        match fs::remove_file(&path) {
+            Ok(_) => {
+                count += 1;
+                total += len;
+            }
+            Err(_) => eprintln!("Could not remove {path:?} from the cache."),
+        }
+    }
+
+    // Index metadata cached by interacts.
+    let metadata = config.cache_path.join("metadata");
+    if metadata.exists() && fs::remove_dir_all(&metadata).is_err() {
+        eprintln!("Could not remove {metadata:?} from the cache.");
+    }
+
+    eprintln!(
+        "{} {count} archive(s) ({} MiB) from {:?}.",
+        err_color_print("Removed", PossibleColor::BrightPurple),
+        total / (1024 * 1024),
+        config.cache_path
+    );
+}
+
+fn entries(dir: &Path) -> Vec<(PathBuf, u64, std::time::SystemTime)> {
+    let Ok(dir) = fs::read_dir(dir)
+    else {
+        return Vec::new();
+    };
+
+    dir.filter_map(|e| {
+        let e = e.ok()?;
+        let meta = e.metadata().ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        Some((e.path(), meta.len(), meta.modified().ok()?))
+    })
+    .collect()
+}
+
+// Strongest hash is used as the key.
+pub fn key(hashes: &Hashes) -> Option<(HashType, &String)> {
+    HASH_ORDER.iter().find_map(|t| {
+        hashes
+            .get(t)
+            // Hashes end up in a path, so only allow hex.
+            .filter(|h| !h.is_empty() && h.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|h| (*t, h))
+    })
+}
+
+pub fn file_name(hash_type: HashType, hash: &str) -> String {
+    format!(
+        "{}-{}",
+        Into::<&str>::into(hash_type),
+        hash.to_ascii_lowercase()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs};
+
+    use filetime::FileTime;
+
+    use super::Cache;
+    use crate::data::HashType;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let path = std::env::temp_dir().join(format!("prebuilt-cache-{}", std::process::id()));
+        let cache = Cache {
+            path: path.clone(),
+            size: 0,
+        };
+
+        let bytes = b"archive".to_vec();
+        let hashes = HashMap::from([(HashType::Sha256, HashType::Sha256.digest(&bytes))]);
+        assert_eq!(cache.get(&hashes), None);
+
+        cache.put(&hashes, &bytes);
+        assert_eq!(cache.get(&hashes), Some(bytes.clone()));
+
+        // Hits count as a use when pruning
+        let file = fs::read_dir(&path).unwrap().next().unwrap().unwrap().path();
+        filetime::set_file_mtime(&file, FileTime::from_unix_time(0, 0)).unwrap();
+        assert_eq!(cache.get(&hashes), Some(bytes));
+        let modified = FileTime::from_last_modification_time(&fs::metadata(&file).unwrap());
+        assert!(modified.unix_seconds() > 0);
+
+        // Corrupted archives are removed
+        let file = fs::read_dir(&path).unwrap().next().unwrap().unwrap().path();
+        fs::write(&file, b"corrupted").unwrap();
+        assert_eq!(cache.get(&hashes), None);
+        assert!(!file.exists());
+
+        // Non hex hashes are never used as paths
+        let hashes = HashMap::from([(HashType::Sha256, "../../etc".to_string())]);
+        cache.put(&hashes, b"archive");
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -1,11 +1,15 @@
 use crate::{
     color::{self, err_color_print, PossibleColor},
-    data::{ConfigFile, ConfigFileIndexes, ConfigFilePrebuilt, ReportType},
+    data::{
+        ConfigFile, ConfigFileHttp, ConfigFileIndexes, ConfigFilePrebuilt, HashType, ReportType,
+        HASH_ORDER,
+    },
+    error::PrebuiltError,
     APPLICATION, DEFAULT_INDEX, ORG, QUALIFIER, TARGET,
 };
 use directories::ProjectDirs;
 use home::cargo_home;
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use std::{
     collections::{HashMap, HashSet},
     fs::{create_dir_all, File, OpenOptions},
@@ -15,39 +19,89 @@ use std::{
 };
 
 static CONFIG_FILE: &str = "config.toml";
+static DEFAULT_CACHE_SIZE: u64 = 1024;
+static DEFAULT_METADATA_TTL: u64 = 60;
+static DEFAULT_JOBS: usize = 1;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Install,
+    Mirror(PathBuf),
+    Verify,
+    List,
+    Uninstall { force: bool },
+    Outdated,
+    Upgrade,
+    Pin,
+    Unpin,
+    Rollback,
+    CacheClean,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexConfig {
+    pub index: String,
+    pub auth: Option<String>,
+    pub http: ConfigFileHttp,
+    pub sigs: HashSet<String>,
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub target: String,
-    pub index: String,
-    pub auth: Option<String>,
+    pub indexes: Vec<IndexConfig>,             // Tried in order
+    pub routes: IndexMap<String, IndexConfig>, // Crate id patterns pinned to one index
+    pub pins: IndexMap<String, String>,        // Crate id to held version
     pub path: PathBuf,
     pub report_path: PathBuf,
+    pub cache_path: PathBuf,
+    pub state_path: PathBuf, // Installed crates
+    pub store_path: PathBuf, // Installed versions, for --store and rollback
+    pub no_cache: bool,
+    pub cache_size: u64,   // MiB, 0 is unlimited
+    pub metadata_ttl: u64, // Seconds
+    pub jobs: usize,       // Crates resolved and downloaded at once
     pub ci: bool,
     pub no_create_path: bool,
     pub reports: IndexSet<ReportType>,
-    pub sigs: HashSet<String>,
     pub no_verify: bool,
+    pub allow_missing_hashes: bool,
+    pub hashes: IndexSet<HashType>, // Allowed hashes, in the order they are tried
+    pub all_hashes: bool,           // Every allowed hash present must match
+    pub atomic: bool,               // Install every crate, or none
+    pub store: bool,                // Install into the store and link into path
     pub safe: bool,
     pub out: bool,
     pub get_latest: bool,
+    pub mode: Mode,
     pub pkgs: IndexSet<String>,
 }
 
 #[derive(Clone, Debug)]
 struct Arguments {
     target: Option<String>,
-    index_key: Option<String>,
-    index: Option<String>,
+    index_key: Option<Vec<String>>,
+    index: Option<Vec<String>>,
     auth: Option<String>,
     config: Option<PathBuf>,
     path: Option<PathBuf>,
     report_path: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
+    state_path: Option<PathBuf>,
+    store_path: Option<PathBuf>,
+    no_cache: bool,
+    cache_size: Option<u64>,
+    metadata_ttl: Option<u64>,
+    jobs: Option<usize>,
     ci: bool,
     no_create_path: bool,
     reports: Option<IndexSet<ReportType>>,
     pub_key: HashSet<String>,
     no_verify: bool,
+    allow_missing_hashes: bool,
+    hashes: Option<IndexSet<HashType>>,
+    all_hashes: bool,
+    atomic: bool,
+    store: bool,
     safe: bool,
     out: bool,
     color: bool,
@@ -55,14 +109,13 @@ struct Arguments {
     gen_config: bool,
     get_latest: bool,
     require_config: bool,
-    pkgs: IndexSet<String>,
+    command: (Mode, IndexSet<String>),
 }
 
-// TODO: Consider moving fallback/default values to here.
-fn parse_args() -> Arguments {
+fn pkgs() -> impl bpaf::Parser<IndexSet<String>> {
     use bpaf::*;
 
-    let pkgs = positional::<String>("PKGS")
+    positional::<String>("PKGS")
         .help("A CSV list of packages with optional @VERSION")
         .parse(|s| {
             let mut v = IndexSet::new();
@@ -70,7 +123,103 @@ fn parse_args() -> Arguments {
                 v.insert(i.to_string());
             }
             Ok::<IndexSet<String>, String>(v)
-        });
+        })
+}
+
+// TODO: Consider moving fallback/default values to here.
+fn parse_args() -> Arguments {
+    args_parser().run()
+}
+
+fn args_parser() -> bpaf::OptionParser<Arguments> {
+    use bpaf::*;
+
+    let mirror = {
+        let cmd = literal("mirror").help(
+            "Download and verify crates from the index into DIR, which can then be used as a file: index. (Every crate in the index is mirrored if PKGS is empty)",
+        );
+        let dir = positional::<PathBuf>("DIR");
+        let pkgs = pkgs().fallback(IndexSet::new());
+        construct!(cmd, dir, pkgs).map(|(_, dir, pkgs)| (Mode::Mirror(dir), pkgs))
+    };
+    let verify = {
+        let cmd = literal("verify").help(
+            "Hash installed binaries of PKGS and check them against the index. (Uses the latest version unless @VERSION is given)",
+        );
+        let pkgs = pkgs();
+        construct!(cmd, pkgs)
+            .adjacent()
+            .map(|(_, pkgs)| (Mode::Verify, pkgs))
+    };
+    let list = literal("list")
+        .help("List crates installed by cargo-prebuilt, and binaries that changed since.")
+        .map(|_| (Mode::List, IndexSet::new()));
+    let uninstall = {
+        let cmd = literal("uninstall")
+            .help("Remove the binaries and reports cargo-prebuilt installed for PKGS.");
+        let force = long("force")
+            .help("Remove binaries even if they were changed since they were installed.")
+            .switch();
+        let pkgs = pkgs();
+        construct!(cmd, force, pkgs)
+            .adjacent()
+            .map(|(_, force, pkgs)| (Mode::Uninstall { force }, pkgs))
+    };
+    let outdated = literal("outdated")
+        .help("List installed crates that have a newer version in the index.")
+        .map(|_| (Mode::Outdated, IndexSet::new()));
+    let upgrade = {
+        let cmd = literal("upgrade").help(
+            "Install the latest version of installed crates in PKGS. (Every installed crate is upgraded if PKGS is empty)",
+        );
+        let pkgs = pkgs().fallback(IndexSet::new());
+        construct!(cmd, pkgs)
+            .adjacent()
+            .map(|(_, pkgs)| (Mode::Upgrade, pkgs))
+    };
+    let pin = {
+        let cmd = literal("pin").help(
+            "Hold PKGS at @VERSION, they are installed at that version unless another one is given and are not upgraded.",
+        );
+        let pkgs = pkgs();
+        construct!(cmd, pkgs)
+            .adjacent()
+            .map(|(_, pkgs)| (Mode::Pin, pkgs))
+    };
+    let unpin = {
+        let cmd = literal("unpin").help("Stop holding PKGS at a version.");
+        let pkgs = pkgs();
+        construct!(cmd, pkgs)
+            .adjacent()
+            .map(|(_, pkgs)| (Mode::Unpin, pkgs))
+    };
+    let rollback = {
+        let cmd = literal("rollback")
+            .help("Restore the version of PKGS that was installed before the last upgrade.");
+        let pkgs = pkgs();
+        construct!(cmd, pkgs)
+            .adjacent()
+            .map(|(_, pkgs)| (Mode::Rollback, pkgs))
+    };
+    let cache_clean = {
+        let cmd = literal("cache");
+        let clean = literal("clean").help("Remove every archive from the download cache.");
+        construct!(cmd, clean).map(|_| (Mode::CacheClean, IndexSet::new()))
+    };
+    let install = pkgs().map(|pkgs| (Mode::Install, pkgs));
+    let command = construct!([
+        mirror,
+        verify,
+        list,
+        uninstall,
+        outdated,
+        upgrade,
+        pin,
+        unpin,
+        rollback,
+        cache_clean,
+        install
+    ]);
 
     let target = long("target")
         .env("PREBUILT_TARGET")
@@ -80,19 +229,23 @@ fn parse_args() -> Arguments {
 
     let index_key = long("index-key")
         .env("PREBUILT_INDEX_KEY")
-        .help("Index to use, pulling from config file. Overrides --index.")
+        .help("Index to use, pulling from config file. A CSV list is tried in order. Overrides --index.")
         .argument::<String>("INDEX_KEY")
+        .map(|s| s.split(',').map(|l| l.to_owned()).collect::<Vec<_>>())
         .optional();
 
     let index = long("index")
         .env("PREBUILT_INDEX")
-        .help(format!("Index to use. (Default: {DEFAULT_INDEX})").as_str())
+        .help(format!("Index to use. A CSV list is tried in order, falling back to the next index if a crate is not found. (Default: {DEFAULT_INDEX})").as_str())
         .argument::<String>("INDEX")
+        .map(|s| s.split(',').map(|l| l.to_owned()).collect::<Vec<_>>())
         .optional();
 
     let auth = long("auth")
         .env("PREBUILT_AUTH")
-        .help("Auth token to use for private indexes.")
+        .help(
+            "Auth token to use for a private index. (Only used for the first index, and only if the config file has no auth for it)",
+        )
         .argument::<String>("TOKEN")
         .optional();
 
@@ -114,6 +267,47 @@ fn parse_args() -> Arguments {
         .argument::<PathBuf>("REPORT_PATH")
         .optional();
 
+    let cache_path = long("cache-path")
+        .env("PREBUILT_CACHE_PATH")
+        .help(format!("Path to the folder where downloaded archives will be cached (Default: See https://github.com/cargo-prebuilt/cargo-prebuilt/blob/v{}/docs/PATHS.md#cache)", env!("CARGO_PKG_VERSION")).as_str())
+        .argument::<PathBuf>("CACHE_PATH")
+        .optional();
+
+    let state_path = long("state-path")
+        .env("PREBUILT_STATE_PATH")
+        .help(format!("Path to the file where installed crates are recorded (Default: See https://github.com/cargo-prebuilt/cargo-prebuilt/blob/v{}/docs/PATHS.md#state)", env!("CARGO_PKG_VERSION")).as_str())
+        .argument::<PathBuf>("STATE_PATH")
+        .optional();
+
+    let store_path = long("store-path")
+        .env("PREBUILT_STORE_PATH")
+        .help(format!("Path to the folder where versions installed with --store, or replaced by an upgrade, are kept (Default: See https://github.com/cargo-prebuilt/cargo-prebuilt/blob/v{}/docs/PATHS.md#store)", env!("CARGO_PKG_VERSION")).as_str())
+        .argument::<PathBuf>("STORE_PATH")
+        .optional();
+
+    let no_cache = long("no-cache")
+        .env("PREBUILT_NO_CACHE")
+        .help("Do not use or add to the download and index metadata caches.")
+        .switch();
+
+    let cache_size = long("cache-size")
+        .env("PREBUILT_CACHE_SIZE")
+        .help(format!("Max size of the download cache in MiB, the oldest archives are removed first. 0 is unlimited. (Default: {DEFAULT_CACHE_SIZE})").as_str())
+        .argument::<u64>("MIB")
+        .optional();
+
+    let metadata_ttl = long("metadata-ttl")
+        .env("PREBUILT_METADATA_TTL")
+        .help(format!("Seconds to use cached index metadata (EX: latest versions) before asking the index if it changed. (Default: {DEFAULT_METADATA_TTL})").as_str())
+        .argument::<u64>("SECONDS")
+        .optional();
+
+    let jobs = long("jobs")
+        .env("PREBUILT_JOBS")
+        .help(format!("Number of crates to resolve and download at once. Crates are still installed in the order given. (Default: {DEFAULT_JOBS})").as_str())
+        .argument::<usize>("N")
+        .optional();
+
     let ci = long("ci")
         .env("PREBUILT_CI")
         .help("Do not download reports, check for a config file, and ignore safe mode.")
@@ -130,7 +324,7 @@ fn parse_args() -> Arguments {
         .argument::<String>("REPORTS")
         .parse(|s| {
             let mut v = IndexSet::new();
-            if !s.eq("") {
+            if !s.is_empty() {
                 for i in s.split(',') {
                     match TryInto::<ReportType>::try_into(i) {
                         Ok(d) => {
@@ -146,7 +340,7 @@ fn parse_args() -> Arguments {
 
     let pub_key = long("pub-key")
         .env("PREBUILT_PUB_KEY")
-        .help("A public verifying key encoded as base64. Must be used with --index.")
+        .help("A public verifying key encoded as base64. Must be used with --index. (Used for every index when multiple are used)")
         .argument::<String>("PUB_KEY")
         .map(|s| s.split(',').map(|l| l.to_owned()).collect::<HashSet<_>>())
         .fallback(HashSet::new());
@@ -156,6 +350,44 @@ fn parse_args() -> Arguments {
         .help("Do not verify downloaded info.json's and hashes.json's.")
         .switch();
 
+    let allow_missing_hashes = long("allow-missing-hashes")
+        .env("PREBUILT_ALLOW_MISSING_HASHES")
+        .help("Install archives even if hashes.json has no hashes for the target. (Archives and binaries are not hashed)")
+        .switch();
+
+    let hashes = long("hashes")
+        .env("PREBUILT_HASHES")
+        .help("Hashes allowed to verify archives and binaries with in a CSV format, tried in order. (Default: sha3_512,sha3_256,sha512,sha256)")
+        .argument::<String>("HASHES")
+        .parse(|s| {
+            let mut v = IndexSet::new();
+            for i in s.split(',') {
+                match TryInto::<HashType>::try_into(i) {
+                    Ok(d) => {
+                        let _ = v.insert(d);
+                    }
+                    Err(_) => return Err(format!("{i} is not a supported hash type.")),
+                }
+            }
+            Ok(v)
+        })
+        .optional();
+
+    let all_hashes = long("all-hashes")
+        .env("PREBUILT_ALL_HASHES")
+        .help("Require every hash in --hashes to be in hashes.json and match, instead of the first one found.")
+        .switch();
+
+    let atomic = long("atomic")
+        .env("PREBUILT_ATOMIC")
+        .help("Install every crate in PKGS, or none of them. Binaries are only replaced after every crate is downloaded and verified.")
+        .switch();
+
+    let store = long("store")
+        .env("PREBUILT_STORE")
+        .help("Install binaries into the store, with a version per directory, and link to them from path. (Symlinks, then hardlinks, then copies)")
+        .switch();
+
     let safe = short('s')
         .long("safe")
         .env("PREBUILT_SAFE")
@@ -202,11 +434,23 @@ fn parse_args() -> Arguments {
         config,
         path,
         report_path,
+        cache_path,
+        state_path,
+        store_path,
+        no_cache,
+        cache_size,
+        metadata_ttl,
+        jobs,
         ci,
         no_create_path,
         reports,
         pub_key,
         no_verify,
+        allow_missing_hashes,
+        hashes,
+        all_hashes,
+        atomic,
+        store,
         safe,
         out,
         color,
@@ -214,16 +458,24 @@ fn parse_args() -> Arguments {
         gen_config,
         get_latest,
         require_config,
-        pkgs,
+        command,
     });
 
     cargo_helper("prebuilt", parser)
         .to_options()
         .version(env!("CARGO_PKG_VERSION"))
-        .run()
 }
 
-fn fill_from_file(args: &mut Arguments) {
+/// Tables from the config file, outside of [prebuilt].
+#[derive(Default)]
+struct FileTables {
+    indexes: HashMap<String, ConfigFileIndexes>,
+    routes: IndexMap<String, String>,
+    pins: IndexMap<String, String>,
+}
+
+/// Fill arguments from the config file, returning the other tables it contains.
+fn fill_from_file(args: &mut Arguments) -> Result<FileTables, PrebuiltError> {
     let conf = if let Some(p) = args.config.clone() {
         p
     }
@@ -236,7 +488,7 @@ fn fill_from_file(args: &mut Arguments) {
             }
             None => {
                 eprintln!("Could not find default config directory! Config file will be ignored.");
-                return;
+                return Ok(FileTables::default());
             }
         }
     }
@@ -245,10 +497,10 @@ fn fill_from_file(args: &mut Arguments) {
     };
 
     if conf.exists() {
-        let mut file = File::open(&conf).expect("Could not open config file.");
         let mut str = String::new();
-        file.read_to_string(&mut str)
-            .expect("Could not read config file.");
+        File::open(&conf)
+            .and_then(|mut file| file.read_to_string(&mut str))
+            .map_err(|_| PrebuiltError::Io(format!("Could not read config file {conf:?}.")))?;
 
         let config: Result<ConfigFile, toml::de::Error> = toml::from_str(&str);
         match config {
@@ -275,90 +527,255 @@ fn fill_from_file(args: &mut Arguments) {
                         };
                     }
 
-                    file_pull![target, index_key, path, report_path, reports];
-                    file_pull_switch![no_create_path, no_verify, safe, out, color, no_color];
-                }
-
-                match (&args.index, &args.index_key) {
-                    (Some(index), None) => {
-                        if let Some(cfi) = config.index {
-                            for (_, i) in cfi {
-                                if i.index.eq(index) {
-                                    if let Some(pk) = i.pub_key {
-                                        for pk in pk {
-                                            args.pub_key.insert(pk);
-                                        }
-                                    }
-                                    if args.auth.is_none() && i.auth.is_some() {
-                                        args.auth = i.auth;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    (None, Some(index_key)) => {
-                        if let Some(cfi) = config.index {
-                            for (key, i) in cfi {
-                                if key.eq(index_key) {
-                                    args.index = Some(i.index);
-                                    if let Some(pk) = i.pub_key {
-                                        for pk in pk {
-                                            args.pub_key.insert(pk);
-                                        }
-                                    }
-                                    if args.auth.is_none() && i.auth.is_some() {
-                                        args.auth = i.auth;
-                                    }
-                                }
-                            }
-                        }
+                    // Indexes from args replace the ones in the config file entirely.
+                    if args.index.is_none() && args.index_key.is_none() {
+                        file_pull![index, index_key];
                     }
-                    _ => panic!(),
+
+                    file_pull![
+                        target,
+                        path,
+                        report_path,
+                        cache_path,
+                        state_path,
+                        store_path,
+                        cache_size,
+                        metadata_ttl,
+                        jobs,
+                        reports,
+                        hashes
+                    ];
+                    file_pull_switch![
+                        no_create_path,
+                        no_cache,
+                        no_verify,
+                        allow_missing_hashes,
+                        all_hashes,
+                        atomic,
+                        store,
+                        safe,
+                        out,
+                        color,
+                        no_color
+                    ];
                 }
+
+                return Ok(FileTables {
+                    indexes: config.index.unwrap_or_default(),
+                    routes: config.routes.unwrap_or_default(),
+                    pins: config.pins.unwrap_or_default(),
+                });
+            }
+            // Ignoring it would drop its routes, and crates would quietly resolve from other indexes.
+            Err(err) => {
+                return Err(PrebuiltError::Config(format!(
+                    "Failed to parse config file {conf:?}.\n{err}"
+                )))
             }
-            Err(err) => eprintln!("Failed to parse config file.\n{err}"),
         }
     }
     else {
         eprintln!("WARN: Could not find config, it will be ignored.");
+
+        if args.config.is_some() {
+            return Err(PrebuiltError::Config("Could not find an existing config files. Maybe try to generate one using --gen-config?".to_string()));
+        }
+
+        if args.require_config {
+            return Err(PrebuiltError::Config(format!(
+                "Config file required, but not found at {conf:?}. Did you mean to use --config=$PATH?"
+            )));
+        }
     }
 
-    if args.config.is_some() {
-        panic!("Could not find an existing config files. Maybe try to generate one using --gen-config?");
+    Ok(FileTables::default())
+}
+
+/// Resolve the indexes to use, in order, along with their keys and auth.
+fn indexes(
+    args: &Arguments,
+    file_indexes: &HashMap<String, ConfigFileIndexes>,
+) -> Result<Vec<IndexConfig>, PrebuiltError> {
+    // Pair each index with its entries in the config file.
+    let indexes: Vec<(String, Vec<&ConfigFileIndexes>)> = match (&args.index, &args.index_key) {
+        (Some(indexes), None) => indexes
+            .iter()
+            .map(|index| {
+                let entries = file_indexes
+                    .values()
+                    .filter(|i| i.index.eq(index))
+                    .collect();
+                (index.clone(), entries)
+            })
+            .collect(),
+        (None, Some(keys)) => keys
+            .iter()
+            .map(|key| match file_indexes.get(key) {
+                Some(i) => Ok((i.index.clone(), vec![i])),
+                None => Err(PrebuiltError::Config(format!(
+                    "Index key {key} was not found in the config file."
+                ))),
+            })
+            .collect::<Result<_, _>>()?,
+        (None, None) => {
+            let entries = file_indexes
+                .values()
+                .filter(|i| i.index.eq(DEFAULT_INDEX))
+                .collect();
+            vec![(DEFAULT_INDEX.to_string(), entries)]
+        }
+        (Some(_), Some(_)) => {
+            return Err(PrebuiltError::Config(
+                "Config options index and index_key conflict.".to_string(),
+            ))
+        }
+    };
+
+    // --auth is only sent to the first index, so a token never leaks to the fallbacks.
+    Ok(indexes
+        .into_iter()
+        .enumerate()
+        .map(|(n, (index, entries))| {
+            let auth = args.auth.as_ref().filter(|_| n == 0);
+            index_config(args, index, entries, auth)
+        })
+        .collect())
+}
+
+/// Resolve the index each route pattern pins its crates to.
+fn routes(
+    args: &Arguments,
+    file_indexes: &HashMap<String, ConfigFileIndexes>,
+    file_routes: IndexMap<String, String>,
+) -> Result<IndexMap<String, IndexConfig>, PrebuiltError> {
+    file_routes
+        .into_iter()
+        .map(|(pattern, key)| match file_indexes.get(&key) {
+            Some(i) => Ok((pattern, index_config(args, i.index.clone(), vec![i], None))),
+            None => Err(PrebuiltError::Config(format!(
+                "Index key {key} for route {pattern} was not found in the config file."
+            ))),
+        })
+        .collect()
+}
+
+/// Combine the keys and auth for an index from args and its entries in the config file.
+/// Auth from the config file wins over cli_auth.
+fn index_config(
+    args: &Arguments,
+    index: String,
+    entries: Vec<&ConfigFileIndexes>,
+    cli_auth: Option<&String>,
+) -> IndexConfig {
+    let mut sigs = args.pub_key.clone();
+    let mut auth = None;
+    let mut http = None;
+    for i in entries {
+        if let Some(pk) = &i.pub_key {
+            sigs.extend(pk.iter().cloned());
+        }
+        if auth.is_none() && i.auth.is_some() {
+            auth = i.auth.clone();
+        }
+        if http.is_none() && i.http.is_some() {
+            http = i.http.clone();
+        }
     }
 
-    if args.require_config {
-        panic!(
-            "Config file required, but not found at {conf:?}. Did you mean to use --config=$PATH?"
-        );
+    // Add cargo-prebuilt-index pub key if needed.
+    if index.eq(DEFAULT_INDEX) {
+        sigs.insert(include_str!("../keys/cargo-prebuilt-index.pub").to_string());
+    }
+
+    IndexConfig {
+        index,
+        auth: auth.or_else(|| cli_auth.cloned()),
+        http: http.unwrap_or_default(),
+        sigs,
     }
 }
 
-fn convert(args: Arguments) -> Config {
-    let target = args.target.unwrap_or_else(|| TARGET.to_owned());
+/// Match a crate id against a route pattern, where * matches any run of characters.
+pub fn route_matches(pattern: &str, id: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = id.strip_prefix(parts.next().unwrap_or_default())
+    else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last()
+    else {
+        // No wildcards
+        return rest.is_empty();
+    };
 
-    let index = args.index.unwrap_or_else(|| DEFAULT_INDEX.to_string());
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
 
-    let auth = args.auth;
+    rest.ends_with(last)
+}
 
-    let path = args.path.unwrap_or_else(|| {
-        let mut cargo_home = cargo_home().expect("Could not find cargo home directory. Please set $CARGO_HOME, or use $PREBUILT_PATH or --path");
-        if !cargo_home.ends_with("bin") {
-            cargo_home.push("bin");
+fn convert(
+    args: Arguments,
+    indexes: Vec<IndexConfig>,
+    routes: IndexMap<String, IndexConfig>,
+    pins: IndexMap<String, String>,
+) -> Result<Config, PrebuiltError> {
+    let target = args.target.unwrap_or_else(|| TARGET.to_owned());
+
+    let path = match args.path {
+        Some(path) => path,
+        None => {
+            let mut cargo_home = cargo_home().map_err(|_| PrebuiltError::Config("Could not find cargo home directory. Please set $CARGO_HOME, or use $PREBUILT_PATH or --path".to_string()))?;
+            if !cargo_home.ends_with("bin") {
+                cargo_home.push("bin");
+            }
+            cargo_home
         }
-        cargo_home
-    });
+    };
 
-    let report_path =
-        args.report_path
-            .unwrap_or_else(|| match ProjectDirs::from(QUALIFIER, ORG, APPLICATION) {
-                Some(project) => {
-                    let mut data = PathBuf::from(project.data_dir());
-                    data.push("reports");
-                    data
-                }
-                None => panic!("Could not get report path, try setting $XDG_DATA_HOME or $HOME."),
-            });
+    let data_dir = |dir: &str| match ProjectDirs::from(QUALIFIER, ORG, APPLICATION) {
+        Some(project) => {
+            let mut data = PathBuf::from(project.data_dir());
+            data.push(dir);
+            Ok(data)
+        }
+        None => Err(PrebuiltError::Config(format!(
+            "Could not get {dir} path, try setting $XDG_DATA_HOME or $HOME."
+        ))),
+    };
+
+    let report_path = match args.report_path {
+        Some(path) => path,
+        None => data_dir("reports")?,
+    };
+
+    let cache_path = match args.cache_path {
+        Some(path) => path,
+        None => data_dir("cache")?,
+    };
+    let state_path = match args.state_path {
+        Some(path) => path,
+        None => data_dir("installed.json")?,
+    };
+    let store_path = match args.store_path {
+        Some(path) => path,
+        None => data_dir("store")?,
+    };
+    let no_cache = args.no_cache;
+    let cache_size = args.cache_size.unwrap_or(DEFAULT_CACHE_SIZE);
+    let metadata_ttl = args.metadata_ttl.unwrap_or(DEFAULT_METADATA_TTL);
+    let jobs = args.jobs.unwrap_or(DEFAULT_JOBS);
+    if jobs == 0 {
+        return Err(PrebuiltError::Config(
+            "At least one job is needed in jobs.".to_string(),
+        ));
+    }
 
     let ci = args.ci;
     let no_create_path = args.no_create_path;
@@ -368,39 +785,62 @@ fn convert(args: Arguments) -> Config {
         .unwrap_or_else(|| IndexSet::from([ReportType::LicenseDL]));
 
     let no_verify = args.no_verify;
+    let allow_missing_hashes = args.allow_missing_hashes;
+    if args.hashes.as_ref().is_some_and(|h| h.is_empty()) {
+        return Err(PrebuiltError::Config(
+            "At least one hash type is needed in hashes.".to_string(),
+        ));
+    }
+    let hashes = args
+        .hashes
+        .unwrap_or_else(|| HASH_ORDER.iter().copied().collect());
+    let all_hashes = args.all_hashes;
+    let atomic = args.atomic;
+    let store = args.store;
     let safe = args.safe;
     let out = args.out;
     let get_latest = args.get_latest;
 
-    let sigs = args.pub_key;
-
     match (args.color, args.no_color) {
         (true, false) => color::set_override(true),
         (_, true) => color::set_override(false),
         _ => {}
     }
 
-    let pkgs = args.pkgs;
+    let (mode, pkgs) = args.command;
 
-    Config {
+    Ok(Config {
         target,
-        index,
-        auth,
+        indexes,
+        routes,
+        pins,
         path,
         report_path,
+        cache_path,
+        state_path,
+        store_path,
+        no_cache,
+        cache_size,
+        metadata_ttl,
+        jobs,
         ci,
         no_create_path,
         reports,
-        sigs,
         no_verify,
+        allow_missing_hashes,
+        hashes,
+        all_hashes,
+        atomic,
+        store,
         safe,
         out,
         get_latest,
+        mode,
         pkgs,
-    }
+    })
 }
 
-pub fn get() -> Config {
+pub fn get() -> Result<Config, PrebuiltError> {
     // arguments and env vars
     let mut args = parse_args();
     #[cfg(debug_assertions)]
@@ -409,122 +849,129 @@ pub fn get() -> Config {
     // Check 1
     // --index and --index-key conflict
     if args.index.is_some() && args.index_key.is_some() {
-        panic!(
-            "Arguments {} and {} {}.",
-            err_color_print("--index", PossibleColor::BrightBlue),
-            err_color_print("--index-key", PossibleColor::BrightBlue),
-            err_color_print("conflict", PossibleColor::BrightRed),
-        );
+        return Err(PrebuiltError::Config(
+            "Arguments --index and --index-key conflict.".to_string(),
+        ));
     }
 
     // Generate a config file from the entered arguments
     if args.gen_config {
-        generate(&args);
-        // NO RETURN
+        generate(&args)?;
+        std::process::exit(0);
     }
 
     // Load from config file
-    if !args.ci {
-        fill_from_file(&mut args);
+    let file = if !args.ci {
+        let file = fill_from_file(&mut args)?;
         #[cfg(debug_assertions)]
         dbg!(&args);
+        file
     }
+    else {
+        FileTables::default()
+    };
 
     // Check 2
-    // Check index and add cargo-prebuilt-index pub key if needed.
-    if args.index.is_none() || args.index.as_ref().unwrap().eq(DEFAULT_INDEX) {
-        args.pub_key
-            .insert(include_str!("../keys/cargo-prebuilt-index.pub").to_string());
-    }
+    // Resolve index chain, routes, and their pub keys.
+    let indexes = indexes(&args, &file.indexes)?;
+    let routes = routes(&args, &file.indexes, file.routes)?;
 
-    convert(args)
+    convert(args, indexes, routes, file.pins)
 }
 
-fn generate(args: &Arguments) -> ! {
+fn generate(args: &Arguments) -> Result<(), PrebuiltError> {
     color::set_override(true);
     eprintln!(
         "{} config, this will ignore package args.",
         err_color_print("Generating", PossibleColor::BrightPurple)
     );
 
-    let conf = args.config.clone().unwrap_or_else(|| {
-        match ProjectDirs::from(QUALIFIER, ORG, APPLICATION) {
-            Some(project) => {
-                let mut conf = PathBuf::from(project.config_dir());
-                create_dir_all(&conf).expect("Could not create paths for config file.");
-                conf.push(CONFIG_FILE);
-                conf
-            }
-            None => panic!(
-                "{} get config directory! Try using --config or $PREBUILT_CONFIG.",
-                err_color_print("Could not", color::PossibleColor::BrightRed)
-            ),
+    let conf = match (&args.config, ProjectDirs::from(QUALIFIER, ORG, APPLICATION)) {
+        (Some(conf), _) => conf.clone(),
+        (None, Some(project)) => {
+            let mut conf = PathBuf::from(project.config_dir());
+            create_dir_all(&conf).map_err(|_| {
+                PrebuiltError::Io(format!("Could not create the directory '{conf:?}'."))
+            })?;
+            conf.push(CONFIG_FILE);
+            conf
         }
-    });
+        (None, None) => {
+            return Err(PrebuiltError::Config(
+                "Could not get config directory! Try using --config or $PREBUILT_CONFIG."
+                    .to_string(),
+            ))
+        }
+    };
     eprintln!("Config Path: {conf:?}");
 
+    let io = |item: &str| PrebuiltError::Io(format!("Could not {item} config file {conf:?}."));
     let mut file = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
-        .open(conf)
-        .expect("Could not create/open config file.");
+        .truncate(false)
+        .open(&conf)
+        .map_err(|_| io("create/open"))?;
     let mut str = String::new();
-    file.read_to_string(&mut str)
-        .expect("Could not read config file.");
-    let mut config: ConfigFile = toml::from_str(&str).expect("Could not parse config file.");
-
-    // Prebuilt block
-    if config.prebuilt.is_none() {
-        config.prebuilt = Some(ConfigFilePrebuilt::default())
-    }
+    file.read_to_string(&mut str).map_err(|_| io("read"))?;
+    let mut config: ConfigFile = toml::from_str(&str).map_err(|err| {
+        PrebuiltError::Config(format!("Failed to parse config file {conf:?}.\n{err}"))
+    })?;
 
     // Index writing
     match (&args.index, &args.index_key) {
-        (Some(index), ik) => {
-            let key = match ik {
-                Some(ik) => ik.clone(),
-                None => format!(
-                    "gen_{}",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Failed to generate a random id for index addition.")
-                        .as_secs()
-                ),
-            };
-
-            match config.index.as_mut() {
-                Some(map) => {
-                    map.insert(
-                        key.clone(),
-                        ConfigFileIndexes {
-                            index: index.clone(),
-                            pub_key: Some(args.pub_key.clone()),
-                            auth: args.auth.clone(),
-                        },
-                    );
-                }
-                None => {
-                    let mut map = HashMap::new();
-                    map.insert(
-                        key.clone(),
-                        ConfigFileIndexes {
-                            index: index.clone(),
-                            pub_key: Some(args.pub_key.clone()),
-                            auth: args.auth.clone(),
-                        },
-                    );
-                    config.index = Some(map);
+        (Some(indexes), ik) => {
+            for (n, index) in indexes.iter().enumerate() {
+                let key = match ik.as_ref().and_then(|ik| ik.get(n)) {
+                    Some(ik) => ik.clone(),
+                    None => format!(
+                        "gen_{}_{n}",
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or_default()
+                    ),
+                };
+
+                match config.index.as_mut() {
+                    Some(map) => {
+                        map.insert(
+                            key.clone(),
+                            ConfigFileIndexes {
+                                index: index.clone(),
+                                pub_key: Some(args.pub_key.clone()),
+                                auth: args.auth.clone(),
+                                http: None,
+                            },
+                        );
+                    }
+                    None => {
+                        let mut map = HashMap::new();
+                        map.insert(
+                            key.clone(),
+                            ConfigFileIndexes {
+                                index: index.clone(),
+                                pub_key: Some(args.pub_key.clone()),
+                                auth: args.auth.clone(),
+                                http: None,
+                            },
+                        );
+                        config.index = Some(map);
+                    }
                 }
-            }
 
-            eprintln!(
-                "{} an index ({index}) under key {key}.",
-                err_color_print("Added", PossibleColor::BrightMagenta)
-            );
+                eprintln!(
+                    "{} an index ({index}) under key {key}.",
+                    err_color_print("Added", PossibleColor::BrightMagenta)
+                );
+            }
         }
         (None, Some(index_key)) => {
-            config.prebuilt.as_mut().unwrap().index_key = Some(index_key.clone());
+            config
+                .prebuilt
+                .get_or_insert_with(ConfigFilePrebuilt::default)
+                .index_key = Some(index_key.clone());
             eprintln!(
                 "{} an index_key.",
                 err_color_print("Added", PossibleColor::BrightMagenta)
@@ -533,102 +980,129 @@ fn generate(args: &Arguments) -> ! {
         _ => {}
     }
 
-    match &mut config.prebuilt {
-        Some(prebuilt) => {
-            // Path writing
-            if let Some(item) = &args.path {
-                prebuilt.path = Some(item.to_path_buf());
-                eprintln!(
-                    "{} a path.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // Report Path writing
-            if let Some(item) = &args.report_path {
-                prebuilt.report_path = Some(item.to_path_buf());
-                eprintln!(
-                    "{} a report path.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // No Create Path writing
-            if args.no_create_path {
-                prebuilt.no_create_path = Some(true);
-                eprintln!(
-                    "{} no create path.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // Reports writing
-            if let Some(item) = &args.reports {
-                prebuilt.reports = Some(item.clone());
-                eprintln!(
-                    "{} reports.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // No Verify writing
-            if args.no_verify {
-                prebuilt.no_verify = Some(true);
-                eprintln!(
-                    "{} no verify.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // Safe writing
-            if args.safe {
-                prebuilt.safe = Some(true);
-                eprintln!(
-                    "{} safe mode.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // Out writing
-            if args.out {
-                prebuilt.out = Some(true);
-                eprintln!(
-                    "{} print events.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            // Color
-            if args.color {
-                prebuilt.color = Some(true);
-                eprintln!(
-                    "{} color.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-            if args.no_color {
-                prebuilt.no_color = Some(true);
-                eprintln!(
-                    "{} no color.",
-                    err_color_print("Added", PossibleColor::BrightMagenta)
-                );
-            }
-        }
-        None => panic!("How you get here?"),
+    let prebuilt = config
+        .prebuilt
+        .get_or_insert_with(ConfigFilePrebuilt::default);
+    // Path writing
+    if let Some(item) = &args.path {
+        prebuilt.path = Some(item.to_path_buf());
+        eprintln!(
+            "{} a path.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // Report Path writing
+    if let Some(item) = &args.report_path {
+        prebuilt.report_path = Some(item.to_path_buf());
+        eprintln!(
+            "{} a report path.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // No Create Path writing
+    if args.no_create_path {
+        prebuilt.no_create_path = Some(true);
+        eprintln!(
+            "{} no create path.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // Reports writing
+    if let Some(item) = &args.reports {
+        prebuilt.reports = Some(item.clone());
+        eprintln!(
+            "{} reports.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // No Verify writing
+    if args.no_verify {
+        prebuilt.no_verify = Some(true);
+        eprintln!(
+            "{} no verify.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // Safe writing
+    if args.safe {
+        prebuilt.safe = Some(true);
+        eprintln!(
+            "{} safe mode.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // Out writing
+    if args.out {
+        prebuilt.out = Some(true);
+        eprintln!(
+            "{} print events.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    // Color
+    if args.color {
+        prebuilt.color = Some(true);
+        eprintln!(
+            "{} color.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
+    }
+    if args.no_color {
+        prebuilt.no_color = Some(true);
+        eprintln!(
+            "{} no color.",
+            err_color_print("Added", PossibleColor::BrightMagenta)
+        );
     }
 
     // Rewind time
-    file.rewind().expect("Could not rewind config file stream.");
+    file.rewind().map_err(|_| io("rewind"))?;
 
     // Write to config
-    let str = toml::to_string(&config).expect("Could not convert ConfigFile to string.");
-    file.write_all(str.as_bytes())
-        .expect("Could not write to config file.");
+    let str = toml::to_string(&config)
+        .map_err(|_| PrebuiltError::Config("Could not convert the config to toml.".to_string()))?;
+    file.write_all(str.as_bytes()).map_err(|_| io("write to"))?;
 
     eprintln!(
         "{}",
         err_color_print("Generated Config!", PossibleColor::Green)
     );
-    std::process::exit(0);
+    Ok(())
+}
+
+/// Config for tests from args, with every path under dir and no config file.
+#[cfg(test)]
+pub fn test_config(dir: &std::path::Path, args: &[&str]) -> Config {
+    let paths = [
+        ("--path", "bin"),
+        ("--report-path", "reports"),
+        ("--cache-path", "cache"),
+        ("--state-path", "installed.json"),
+        ("--store-path", "store"),
+    ];
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|(arg, name)| format!("{arg}={}", dir.join(name).display()))
+        .collect();
+
+    let mut all: Vec<&str> = paths.iter().map(|p| p.as_str()).collect();
+    all.extend(["--ci", "--no-cache", "--no-verify"]);
+    all.extend(args);
+
+    let args = args_parser().run_inner(all.as_slice()).unwrap();
+    let indexes = indexes(&args, &HashMap::new()).unwrap();
+    convert(args, indexes, IndexMap::new(), IndexMap::new()).unwrap()
 }
 
 #[cfg(test)]
 mod test {
     use minisign_verify::{PublicKey, Signature};
 
+    use std::collections::HashMap;
+
+    use super::{args_parser, fill_from_file, generate, indexes, route_matches};
+    use crate::{data::ConfigFileIndexes, error::PrebuiltError};
+
     #[test]
     fn test_minisign1() {
         let data = include_bytes!("../test/pubdata.test");
@@ -639,4 +1113,96 @@ mod test {
         let pk = PublicKey::from_base64(pubkey).unwrap();
         pk.verify(data, &signature, false).unwrap();
     }
+
+    #[test]
+    fn test_route_matches() {
+        assert!(route_matches("acme-tool", "acme-tool"));
+        assert!(!route_matches("acme-tool", "acme-tool2"));
+        assert!(route_matches("acme-*", "acme-tool"));
+        assert!(route_matches("acme-*", "acme-"));
+        assert!(!route_matches("acme-*", "acm"));
+        assert!(route_matches("*-internal", "tool-internal"));
+        assert!(route_matches("acme-*-cli", "acme-tool-cli"));
+        assert!(!route_matches("acme-*-cli", "acme-cli"));
+        assert!(route_matches("*", "ripgrep"));
+    }
+
+    #[test]
+    fn test_auth_first_index() {
+        let args = args_parser()
+            .run_inner(&[
+                "--auth",
+                "cli",
+                "--index",
+                "gh-pri:a/b/c,gh-pri:d/e/f",
+                "ripgrep",
+            ])
+            .unwrap();
+
+        // --auth never reaches a fallback index
+        let chain = indexes(&args, &HashMap::new()).unwrap();
+        assert_eq!(chain[0].auth.as_deref(), Some("cli"));
+        assert_eq!(chain[1].auth, None);
+
+        // Auth from the config file wins
+        let file = HashMap::from([
+            (
+                "a".to_string(),
+                ConfigFileIndexes {
+                    index: "gh-pri:a/b/c".to_string(),
+                    pub_key: None,
+                    auth: Some("file".to_string()),
+                    http: None,
+                },
+            ),
+            (
+                "d".to_string(),
+                ConfigFileIndexes {
+                    index: "gh-pri:d/e/f".to_string(),
+                    pub_key: None,
+                    auth: Some("other".to_string()),
+                    http: None,
+                },
+            ),
+        ]);
+        let chain = indexes(&args, &file).unwrap();
+        assert_eq!(chain[0].auth.as_deref(), Some("file"));
+        assert_eq!(chain[1].auth.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn test_malformed_config() {
+        let path =
+            std::env::temp_dir().join(format!("prebuilt-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "[routs]\n\"acme-*\" = \"internal\"\n").unwrap();
+        let config = format!("--config={}", path.display());
+        let mut args = args_parser()
+            .run_inner([config.as_str(), "ripgrep"].as_slice())
+            .unwrap();
+
+        // A typo never drops the routes quietly
+        assert!(matches!(
+            fill_from_file(&mut args),
+            Err(PrebuiltError::Config(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_generate_malformed() {
+        let path =
+            std::env::temp_dir().join(format!("prebuilt-generate-{}.toml", std::process::id()));
+        std::fs::write(&path, "[prebuilt\n").unwrap();
+        let config = format!("--config={}", path.display());
+        let args = args_parser()
+            .run_inner([config.as_str(), "--gen-config", "ripgrep"].as_slice())
+            .unwrap();
+
+        // Errors instead of panicking, and leaves the file alone
+        assert!(matches!(generate(&args), Err(PrebuiltError::Config(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[prebuilt\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -3,8 +3,8 @@ use std::{
     path::PathBuf,
 };
 
-use indexmap::IndexSet;
-use serde::{Deserialize, Serialize};
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use super::HashType;
 
@@ -61,31 +61,71 @@ impl TryFrom<&str> for ReportType {
 pub struct ConfigFile {
     pub prebuilt: Option<ConfigFilePrebuilt>,
     pub index: Option<HashMap<String, ConfigFileIndexes>>,
+    pub routes: Option<IndexMap<String, String>>, // Crate id or pattern to index key
+    pub pins: Option<IndexMap<String, String>>,   // Crate id to held version
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigFilePrebuilt {
     pub target: Option<String>,
-    pub index_key: Option<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub index: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub index_key: Option<Vec<String>>,
     pub path: Option<PathBuf>,
     pub report_path: Option<PathBuf>,
+    pub cache_path: Option<PathBuf>,
+    pub state_path: Option<PathBuf>,
+    pub store_path: Option<PathBuf>,
+    pub no_cache: Option<bool>,
+    pub cache_size: Option<u64>,
+    pub metadata_ttl: Option<u64>,
+    pub jobs: Option<usize>,
     pub no_create_path: Option<bool>,
     pub reports: Option<IndexSet<ReportType>>,
     pub color: Option<bool>,
     pub no_color: Option<bool>,
     pub hashes: Option<IndexSet<HashType>>,
+    pub all_hashes: Option<bool>,
     pub no_verify: Option<bool>,
+    pub allow_missing_hashes: Option<bool>,
+    pub atomic: Option<bool>,
+    pub store: Option<bool>,
     pub safe: Option<bool>,
     pub out: Option<bool>,
 }
 
+/// Allow a single string where a list of strings is expected.
+fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(Option::<OneOrMany>::deserialize(d)?.map(|v| match v {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    }))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigFileIndexes {
     pub index: String,
     pub pub_key: Option<HashSet<String>>,
     pub auth: Option<String>, // TODO: Should be stored in base64? Maybe encrypt?
+    pub http: Option<ConfigFileHttp>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFileHttp {
+    pub latest: Option<String>, // Url template for the latest version of a crate
+    pub file: Option<String>,   // Url template for the files of a crate version
+    pub auth_header: Option<String>, // Header to send the auth token in
 }
 
 #[cfg(test)]
@@ -109,4 +149,59 @@ mod test {
         let toml = "";
         let _: ConfigFile = toml::from_str(toml).unwrap();
     }
+
+    #[test]
+    fn test_deser4() {
+        let toml = include_str!("../../test/config_3.toml");
+        let config: ConfigFile = toml::from_str(toml).unwrap();
+        let http = config.index.unwrap()["files"].http.clone().unwrap();
+        assert_eq!(http.file.unwrap(), "{base}/{id}/{version}/{file}");
+    }
+
+    #[test]
+    fn test_deser5() {
+        let toml = include_str!("../../test/config_4.toml");
+        let config: ConfigFile = toml::from_str(toml).unwrap();
+        let prebuilt = config.prebuilt.unwrap();
+        assert_eq!(
+            prebuilt.index.unwrap(),
+            [
+                "gh-pri:github.com/org/index",
+                "gh-pub:github.com/cargo-prebuilt/index"
+            ]
+        );
+        assert_eq!(prebuilt.index_key.unwrap(), ["internal"]);
+    }
+
+    #[test]
+    fn test_deser6() {
+        let toml = include_str!("../../test/config_5.toml");
+        let config: ConfigFile = toml::from_str(toml).unwrap();
+        let routes = config.routes.unwrap();
+        assert_eq!(
+            routes.get_index(0).unwrap(),
+            (&"acme-tool".to_string(), &"internal".to_string())
+        );
+        assert_eq!(routes["acme-*"], "internal");
+    }
+
+    #[test]
+    #[cfg(feature = "sha2")]
+    fn test_deser7() {
+        use crate::data::HashType;
+
+        let toml = include_str!("../../test/config_6.toml");
+        let config: ConfigFile = toml::from_str(toml).unwrap();
+        let prebuilt = config.prebuilt.unwrap();
+        let hashes: Vec<HashType> = prebuilt.hashes.unwrap().into_iter().collect();
+        assert_eq!(hashes, vec![HashType::Sha512, HashType::Sha256]);
+        assert_eq!(prebuilt.all_hashes, Some(true));
+    }
+
+    #[test]
+    fn test_deser8() {
+        let toml = include_str!("../../test/config_7.toml");
+        let config: ConfigFile = toml::from_str(toml).unwrap();
+        assert_eq!(config.pins.unwrap()["cargo-nextest"], "0.9.70");
+    }
 }
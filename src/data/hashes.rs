@@ -4,6 +4,18 @@ use serde::{Deserialize, Serialize};
 
 pub type Hashes = HashMap<HashType, String>;
 
+/// Hashes this build supports, strongest first.
+pub static HASH_ORDER: &[HashType] = &[
+    #[cfg(feature = "sha3")]
+    HashType::Sha3_512,
+    #[cfg(feature = "sha3")]
+    HashType::Sha3_256,
+    #[cfg(feature = "sha2")]
+    HashType::Sha512,
+    #[cfg(feature = "sha2")]
+    HashType::Sha256,
+];
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum HashType {
     #[cfg(feature = "sha2")]
@@ -19,6 +31,34 @@ pub enum HashType {
     #[serde(rename = "sha3_512")]
     Sha3_512,
 }
+impl HashType {
+    /// Hex encoded hash of bytes.
+    #[allow(unused_variables)] // Without any hash features there is nothing to digest.
+    pub fn digest(self, bytes: &[u8]) -> String {
+        match self {
+            #[cfg(feature = "sha2")]
+            HashType::Sha256 => {
+                use sha2::{Digest, Sha256};
+                hex::encode(Sha256::digest(bytes))
+            }
+            #[cfg(feature = "sha2")]
+            HashType::Sha512 => {
+                use sha2::{Digest, Sha512};
+                hex::encode(Sha512::digest(bytes))
+            }
+            #[cfg(feature = "sha3")]
+            HashType::Sha3_256 => {
+                use sha3::{Digest, Sha3_256};
+                hex::encode(Sha3_256::digest(bytes))
+            }
+            #[cfg(feature = "sha3")]
+            HashType::Sha3_512 => {
+                use sha3::{Digest, Sha3_512};
+                hex::encode(Sha3_512::digest(bytes))
+            }
+        }
+    }
+}
 impl From<HashType> for &str {
     fn from(value: HashType) -> Self {
         match value {
@@ -106,4 +146,10 @@ mod test {
         let json = include_str!("../../test/hashes_2.json");
         let _: HashesFile = serde_json::from_str(json).unwrap();
     }
+
+    #[test]
+    fn test_deser3() {
+        let json = include_str!("../../test/hashes_3.json");
+        let _: HashesFile = serde_json::from_str(json).unwrap();
+    }
 }
@@ -3,18 +3,25 @@ use std::collections::HashMap;
 use serde::Deserialize;
 
 /// This is an intermediate format, only for use in this program.
+/// Fields that are only carried over from info.json are allowed to be unread.
 #[derive(Debug)]
 pub struct InfoFileImm {
-    pub id: String,                    // Crate ID
-    pub version: String,               // Crate Version
-    pub license: String,               // SPDX License String
-    pub git: String,                   // Url to Git
-    pub description: String,           // Crate Description
-    pub bins: Vec<String>,             // Crate Binaries
+    #[allow(dead_code)]
+    pub id: String, // Crate ID
+    #[allow(dead_code)]
+    pub version: String, // Crate Version
+    #[allow(dead_code)]
+    pub license: String, // SPDX License String
+    #[allow(dead_code)]
+    pub git: String, // Url to Git
+    #[allow(dead_code)]
+    pub description: String, // Crate Description
+    pub bins: Vec<String>, // Crate Binaries
+    #[allow(dead_code)]
     pub info: HashMap<String, String>, // Metadata
-    pub archive: InfoFileArchiveV1,    // Archive Info
-    pub files: InfoFileFilesV1,        // File Names
-    pub targets: Vec<String>,          // Targets Built For
+    pub archive: InfoFileArchiveV1, // Archive Info
+    pub files: InfoFileFilesV1, // File Names
+    pub targets: Vec<String>, // Targets Built For
 }
 impl From<InfoFile> for InfoFileImm {
     fn from(value: InfoFile) -> Self {
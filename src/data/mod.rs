@@ -1,7 +1,9 @@
 mod config;
 mod hashes;
 mod info;
+mod state;
 
 pub use config::*;
 pub use hashes::*;
 pub use info::*;
+pub use state::*;
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::data::Hashes;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "state_version")]
+pub enum StateFile {
+    #[serde(rename = "1")]
+    V1(StateFileV1),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateFileV1 {
+    pub crates: IndexMap<String, InstalledCrateV1>, // Crate ID to what is installed
+    #[serde(default)]
+    pub pins: IndexMap<String, String>, // Crate ID to held version
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstalledCrateV1 {
+    pub version: String,           // Crate Version
+    pub index: String,             // Index it was installed from
+    pub target: String,            // Target Installed
+    pub installed: u64,            // Unix time in seconds
+    pub bins: Vec<InstalledBinV1>, // Installed Binaries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Box<InstalledCrateV1>>, // Version replaced by this one, kept in the store
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstalledBinV1 {
+    pub path: PathBuf,  // Absolute Path
+    pub hashes: Hashes, // Hashes of the binary when it was installed
+}
+
+#[cfg(test)]
+mod test {
+    use super::StateFile;
+
+    #[test]
+    fn test_deser1() {
+        let json = include_str!("../../test/state_1.json");
+        let _: StateFile = serde_json::from_str(json).unwrap();
+    }
+}
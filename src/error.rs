@@ -0,0 +1,98 @@
+use std::process::ExitCode;
+
+use crate::interact::InteractError;
+
+/// Failures that stop cargo-prebuilt, each with its own exit code. (See docs/EXIT_CODES.md)
+#[derive(Debug)]
+pub enum PrebuiltError {
+    Config(String),
+    NotFound(String),
+    TargetUnsupported(String),
+    Signature(String),
+    HashMismatch(String),
+    Network(String),
+    Auth(String),
+    AlreadyExists(String),
+    Malformed(String),
+    Io(String),
+    Unsupported(String),
+}
+impl PrebuiltError {
+    pub fn code(&self) -> u8 {
+        match self {
+            PrebuiltError::Config(_) => 10,
+            PrebuiltError::NotFound(_) => 11,
+            PrebuiltError::TargetUnsupported(_) => 12,
+            PrebuiltError::Signature(_) => 13,
+            PrebuiltError::HashMismatch(_) => 14,
+            PrebuiltError::Network(_) => 15,
+            PrebuiltError::Auth(_) => 16,
+            PrebuiltError::AlreadyExists(_) => 17,
+            PrebuiltError::Malformed(_) => 18,
+            PrebuiltError::Io(_) => 19,
+            PrebuiltError::Unsupported(_) => 20,
+        }
+    }
+
+    /// Stable name used by the error event. (See docs/EXIT_CODES.md)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PrebuiltError::Config(_) => "config",
+            PrebuiltError::NotFound(_) => "not_found",
+            PrebuiltError::TargetUnsupported(_) => "target_unsupported",
+            PrebuiltError::Signature(_) => "signature",
+            PrebuiltError::HashMismatch(_) => "hash_mismatch",
+            PrebuiltError::Network(_) => "network",
+            PrebuiltError::Auth(_) => "auth",
+            PrebuiltError::AlreadyExists(_) => "already_exists",
+            PrebuiltError::Malformed(_) => "malformed",
+            PrebuiltError::Io(_) => "io",
+            PrebuiltError::Unsupported(_) => "unsupported",
+        }
+    }
+
+    /// Convert an interact error, with what was being fetched for context.
+    pub fn from_interact(err: InteractError, item: &str, index: &str) -> Self {
+        match err {
+            InteractError::Malformed => PrebuiltError::Malformed(format!("{item} is malformed.")),
+            InteractError::HttpCode(404) => {
+                PrebuiltError::NotFound(format!("{item} was not found in index {index}."))
+            }
+            InteractError::HttpCode(code) => {
+                PrebuiltError::Network(format!("Http error {code} for {item}."))
+            }
+            InteractError::Unauthorized => PrebuiltError::Auth(format!(
+                "Bad token for index {index}. Check --auth or the index auth in your config."
+            )),
+            InteractError::Unsupported => {
+                PrebuiltError::Unsupported(format!("Index {index} does not support {item}."))
+            }
+            InteractError::ConnectionError => {
+                PrebuiltError::Network(format!("Connection error for {item}."))
+            }
+        }
+    }
+}
+impl std::fmt::Display for PrebuiltError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrebuiltError::Config(s)
+            | PrebuiltError::NotFound(s)
+            | PrebuiltError::TargetUnsupported(s)
+            | PrebuiltError::Signature(s)
+            | PrebuiltError::HashMismatch(s)
+            | PrebuiltError::Network(s)
+            | PrebuiltError::Auth(s)
+            | PrebuiltError::AlreadyExists(s)
+            | PrebuiltError::Malformed(s)
+            | PrebuiltError::Io(s)
+            | PrebuiltError::Unsupported(s) => write!(f, "{s}"),
+        }
+    }
+}
+impl std::error::Error for PrebuiltError {}
+impl From<&PrebuiltError> for ExitCode {
+    fn from(value: &PrebuiltError) -> Self {
+        ExitCode::from(value.code())
+    }
+}
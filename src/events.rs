@@ -1,13 +1,56 @@
 use serde_json::json;
-use std::path::Path;
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+};
 
-use crate::config::Config;
+use crate::{config::Config, data::InstalledCrateV1, error::PrebuiltError, state::BinStatus};
 
 static EVENT_VERSION: &str = "1";
 
+thread_local! {
+    // Events held back by a worker thread, so they can be printed in order. (See --jobs)
+    static HELD: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    // If a failure already has an error event.
+    static REPORTED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn emit(line: String) {
+    let line = HELD.with(|held| match held.borrow_mut().as_mut() {
+        Some(held) => {
+            held.push(line);
+            None
+        }
+        None => Some(line),
+    });
+    if let Some(line) = line {
+        println!("{line}");
+    }
+}
+
+/// Run f without printing its events on this thread, returning them instead.
+pub fn hold<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    HELD.with(|held| *held.borrow_mut() = Some(Vec::new()));
+    let res = f();
+    let events = HELD
+        .with(|held| held.borrow_mut().take())
+        .unwrap_or_default();
+    (res, events)
+}
+
+/// Print events returned by hold.
+pub fn replay(events: Vec<String>) {
+    for line in events {
+        let event: Option<serde_json::Value> = serde_json::from_str(&line).ok();
+        if event.is_some_and(|e| e["event"].eq("error")) {
+            REPORTED.with(|reported| reported.set(true));
+        }
+        println!("{line}");
+    }
+}
+
 fn event(id: &str, version: &str, event: &str, data: &str) {
-    println!(
-        "{}",
+    emit(
         serde_json::to_string(&json!({
             "crate": id,
             "version": version,
@@ -15,10 +58,75 @@ fn event(id: &str, version: &str, event: &str, data: &str) {
             "event": event,
             "data": data,
         }))
-        .unwrap_or_else(|_| panic!("Could not generate {event} event."))
+        .unwrap_or_else(|_| panic!("Could not generate {event} event.")),
     );
 }
 
+/// Print an error event, passing the error through so it can be used in map_err.
+pub fn error(id: &str, version: &str, config: &Config, err: PrebuiltError) -> PrebuiltError {
+    failed(id, version, config, &err);
+    err
+}
+
+/// Print an error event for a failure that has none yet, such as one that is not about a crate.
+pub fn unreported(config: &Config, err: PrebuiltError) -> PrebuiltError {
+    match REPORTED.with(|reported| reported.get()) {
+        true => err,
+        false => error("", "", config, err),
+    }
+}
+
+/// Print an error event without taking the error, for a failure shared by several crates.
+pub fn failed(id: &str, version: &str, config: &Config, err: &PrebuiltError) {
+    REPORTED.with(|reported| reported.set(true));
+    if config.out {
+        emit(
+            serde_json::to_string(&json!({
+                "crate": id,
+                "version": version,
+                "event_version": EVENT_VERSION,
+                "event": "error",
+                "kind": err.kind(),
+                "data": err.to_string(),
+            }))
+            .unwrap_or_else(|_| panic!("Could not generate error event.")),
+        );
+    }
+}
+
+pub fn listed(id: &str, config: &Config, installed: &InstalledCrateV1) {
+    if config.out {
+        emit(
+            serde_json::to_string(&json!({
+                "crate": id,
+                "version": installed.version,
+                "event_version": EVENT_VERSION,
+                "event": "listed",
+                "index": installed.index,
+                "target": installed.target,
+                "data": format!("{id}@{}", installed.version),
+            }))
+            .unwrap_or_else(|_| panic!("Could not generate listed event.")),
+        );
+    }
+}
+
+pub fn bin_listed(id: &str, version: &str, config: &Config, path: &Path, status: BinStatus) {
+    if config.out {
+        emit(
+            serde_json::to_string(&json!({
+                "crate": id,
+                "version": version,
+                "event_version": EVENT_VERSION,
+                "event": "bin_listed",
+                "status": Into::<&str>::into(status),
+                "data": path_data(path),
+            }))
+            .unwrap_or_else(|_| panic!("Could not generate bin_listed event.")),
+        );
+    }
+}
+
 pub fn info_verify(id: &str, version: &str, config: &Config, verified: bool) {
     if config.out {
         event(id, version, "info_verified", &verified.to_string());
@@ -31,20 +139,35 @@ pub fn hashes_verify(id: &str, version: &str, config: &Config, verified: bool) {
     }
 }
 
+pub fn index(id: &str, version: &str, config: &Config, index: &str) {
+    if config.out {
+        event(id, version, "index", index);
+    }
+}
+
 pub fn target(id: &str, version: &str, config: &Config) {
     if config.out {
         event(id, version, "target", &config.target);
     }
 }
 
+fn path_data(path: &Path) -> String {
+    let path = format!("{path:?}");
+    let mut path = path.as_str();
+    path = path.strip_prefix('"').unwrap_or(path);
+    path = path.strip_suffix('"').unwrap_or(path);
+    path.to_string()
+}
+
 pub fn binary_installed(id: &str, version: &str, config: &Config, path: &Path) {
     if config.out {
-        let path = format!("{path:?}");
-        let mut path = path.as_str();
-        path = path.strip_prefix('"').unwrap_or(path);
-        path = path.strip_suffix('"').unwrap_or(path);
+        event(id, version, "bin_installed", &path_data(path));
+    }
+}
 
-        event(id, version, "bin_installed", path);
+pub fn binary_verified(id: &str, version: &str, config: &Config, path: &Path) {
+    if config.out {
+        event(id, version, "bin_verified", &path_data(path));
     }
 }
 
@@ -54,6 +177,42 @@ pub fn installed(id: &str, version: &str, config: &Config) {
     }
 }
 
+pub fn uninstalled(id: &str, version: &str, config: &Config) {
+    if config.out {
+        event(id, version, "uninstalled", &format!("{id}@{version}"));
+    }
+}
+
+pub fn outdated(id: &str, version: &str, config: &Config, latest: &str) {
+    if config.out {
+        event(id, version, "outdated", latest);
+    }
+}
+
+pub fn pinned(id: &str, version: &str, config: &Config) {
+    if config.out {
+        event(id, version, "pinned", &format!("{id}@{version}"));
+    }
+}
+
+pub fn unpinned(id: &str, version: &str, config: &Config) {
+    if config.out {
+        event(id, version, "unpinned", &format!("{id}@{version}"));
+    }
+}
+
+pub fn rolled_back(id: &str, version: &str, config: &Config) {
+    if config.out {
+        event(id, version, "rolled_back", &format!("{id}@{version}"));
+    }
+}
+
+pub fn mirrored(id: &str, version: &str, config: &Config, path: &Path) {
+    if config.out {
+        event(id, version, "mirrored", &path_data(path));
+    }
+}
+
 pub fn wrote_report(id: &str, version: &str, config: &Config, report_type: &str) {
     if config.out {
         event(id, version, "wrote_report", report_type);
@@ -75,3 +234,50 @@ pub fn print_audit(id: &str, version: &str, text: &str) {
 pub fn get_latest(id: &str, version: &str) {
     event(id, version, "latest_version", version);
 }
+
+#[cfg(test)]
+mod test {
+    use super::{emit, error, hold, unreported, HELD};
+    use crate::{config::test_config, error::PrebuiltError};
+
+    #[test]
+    fn test_hold() {
+        let (res, held) = hold(|| {
+            emit("a".to_string());
+            emit("b".to_string());
+            1
+        });
+        assert_eq!(res, 1);
+        assert_eq!(held, vec!["a", "b"]);
+
+        // Events are printed again once f returns
+        assert!(HELD.with(|held| held.borrow().is_none()));
+    }
+
+    #[test]
+    fn test_error() {
+        let root = std::env::temp_dir().join(format!("prebuilt-events-{}", std::process::id()));
+        let config = test_config(&root, &["--out", "demo"]);
+        let parse = |line: &String| serde_json::from_str::<serde_json::Value>(line).unwrap();
+
+        // Failures without an event get one that is not about a crate
+        let (_, held) = hold(|| unreported(&config, PrebuiltError::Io("io".to_string())));
+        let event = parse(&held[0]);
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["kind"], "io");
+        assert_eq!(event["crate"], "");
+
+        // Events name the crate, and the kind of failure
+        let (_, held) = hold(|| {
+            let err = PrebuiltError::HashMismatch("changed".to_string());
+            let err = error("demo", "1.0.0", &config, err);
+            unreported(&config, err)
+        });
+        assert_eq!(held.len(), 1);
+        let event = parse(&held[0]);
+        assert_eq!(event["kind"], "hash_mismatch");
+        assert_eq!(event["crate"], "demo");
+        assert_eq!(event["version"], "1.0.0");
+        assert_eq!(event["data"], "changed");
+    }
+}
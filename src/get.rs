@@ -1,79 +1,119 @@
+use indexmap::IndexSet;
 use std::{
+    collections::HashSet,
     fs::{create_dir_all, File},
     io::Write,
 };
 
 use crate::{
+    cache::Cache,
     color::{err_color_print, PossibleColor},
-    config::Config,
-    data::{HashType, Hashes, HashesFile, HashesFileImm, InfoFile, InfoFileImm, ReportType},
+    config::{self, Config, IndexConfig},
+    data::{
+        Hashes, HashesFile, HashesFileBlobV1, HashesFileImm, InfoFile, InfoFileImm, ReportType,
+    },
+    error::PrebuiltError,
     events,
-    interact::{self, Interact, InteractError},
+    interact::{self, HttpCache, Interact, InteractError},
 };
 use ureq::Agent;
 
 pub struct Fetcher {
-    interact: Box<dyn Interact>,
+    indexes: Vec<(IndexConfig, Box<dyn Interact>)>,
+    chain: Vec<usize>,            // Indexes tried in order
+    routes: Vec<(String, usize)>, // Crate id patterns pinned to one index
+    current: usize,               // Index serving the current crate
+    cache: Option<Cache>,
+    agent: Agent,
 }
 impl Fetcher {
-    pub fn new(config: &Config, agent: Agent) -> Self {
-        let interact = interact::create_interact(config.index.clone(), config.auth.as_ref(), agent);
-        Self { interact }
+    pub fn new(config: &Config, agent: Agent) -> Result<Self, PrebuiltError> {
+        Self::build(config, agent, false)
     }
 
-    pub fn get_latest(&mut self, id: &str) -> String {
+    /// Same as new, without announcing each index again, for --jobs threads.
+    pub fn quiet(config: &Config, agent: Agent) -> Result<Self, PrebuiltError> {
+        Self::build(config, agent, true)
+    }
+
+    fn build(config: &Config, agent: Agent, quiet: bool) -> Result<Self, PrebuiltError> {
+        let http_cache = (!config.no_cache)
+            .then(|| HttpCache::new(config.cache_path.join("metadata"), config.metadata_ttl));
+
+        let mut indexes = Vec::new();
+        let mut add = |index: &IndexConfig| {
+            // Routes to an index that is already used share it.
+            if let Some(i) = indexes.iter().position(|(used, _)| used == index) {
+                return Ok(i);
+            }
+
+            let interact = interact::create_interact(
+                index.index.clone(),
+                index.auth.as_ref(),
+                &index.http,
+                http_cache.as_ref(),
+                agent.clone(),
+                quiet,
+            )?;
+            indexes.push((index.clone(), interact));
+            Ok::<_, PrebuiltError>(indexes.len() - 1)
+        };
+
+        let chain = config
+            .indexes
+            .iter()
+            .map(&mut add)
+            .collect::<Result<_, _>>()?;
+        let routes = config
+            .routes
+            .iter()
+            .map(|(pattern, index)| Ok((pattern.clone(), add(index)?)))
+            .collect::<Result<_, PrebuiltError>>()?;
+
+        Ok(Self {
+            indexes,
+            chain,
+            routes,
+            current: 0,
+            cache: Cache::new(config),
+            agent,
+        })
+    }
+
+    pub fn get_latest(&mut self, id: &str) -> Result<String, PrebuiltError> {
         self.fetch_latest(id)
     }
 
+    pub fn get_ids(&mut self) -> Result<Vec<String>, PrebuiltError> {
+        self.fetch_ids()
+    }
+
     pub fn download(
         &mut self,
         id: &str,
         version: &str,
         config: &Config,
-    ) -> (InfoFileImm, HashesFileImm, Vec<u8>) {
+    ) -> Result<(InfoFileImm, HashesFileImm, Vec<u8>), PrebuiltError> {
         eprintln!(
             "{} info for {id}@{version}.",
             err_color_print("Fetching", PossibleColor::BrightBlue),
         );
 
-        // info.json
-        let raw_info_file = &self.fetch_str(id, version, "info.json");
-        let info: InfoFile = serde_json::from_str(raw_info_file)
-            .unwrap_or_else(|_| panic!("info.json is malformed for {id}@{version}"));
-        let info: InfoFileImm = info.into();
-
-        // info.json.minisig and test
-        #[cfg(feature = "sig")]
-        if !config.no_verify {
-            if let Some(sig_file) = info.files.sig_info.clone() {
-                let v =
-                    self.verify_file(id, version, "info.json", config, &sig_file, raw_info_file);
-                events::info_verify(id, version, config, v);
-            }
-            else {
-                panic!(
-                    "Could not force sig for index {}. info.json is not signed for {id}@{version}.",
-                    config.index
-                );
-            }
-        }
-        #[cfg(not(feature = "sig"))]
-        if !config.no_verify {
-            panic!("Could not force sig for index {}. This requires the 'security' and/or 'sig' feature(s). Or you can use the flag '--no-verify'.", config.index);
-        }
+        let (info, _) = self.fetch_info(id, version, config)?;
 
         // check if target is supported
         if !info.targets.contains(&config.target) {
-            panic!(
-                "{id}@{version} does {} target {}",
-                err_color_print("not support", PossibleColor::BrightRed),
+            return Err(PrebuiltError::TargetUnsupported(format!(
+                "{id}@{version} does not support target {}.",
                 config.target
-            );
+            )));
         }
 
         // check if compression is supported
         if !info.archive.compression.eq("gz") {
-            panic!("{id}@{version} does not support compression gzip");
+            return Err(PrebuiltError::Unsupported(format!(
+                "{id}@{version} does not support compression gzip."
+            )));
         }
 
         // check if binary does not exist, if safe mode is on
@@ -83,10 +123,9 @@ impl Fetcher {
                 path.push(bin);
 
                 if path.exists() {
-                    panic!(
-                        "Binary {bin} {} for {id}@{version}",
-                        err_color_print("already exists", PossibleColor::BrightRed)
-                    );
+                    return Err(PrebuiltError::AlreadyExists(format!(
+                        "Binary {bin} already exists for {id}@{version}."
+                    )));
                 }
             }
         }
@@ -97,33 +136,7 @@ impl Fetcher {
             &config.target
         );
 
-        // hashes.json
-        let raw_hashes_file = &self.fetch_str(id, version, &info.files.hash);
-        let hashes: HashesFile = serde_json::from_str(raw_hashes_file)
-            .unwrap_or_else(|_| panic!("{} is malformed for {id}@{version}", info.files.hash));
-        let hashes: HashesFileImm = hashes.into();
-
-        // hashes.json.minisig and test
-        #[cfg(feature = "sig")]
-        if !config.no_verify {
-            if let Some(sig_file) = info.files.sig_hash.clone() {
-                let v = self.verify_file(
-                    id,
-                    version,
-                    &info.files.hash,
-                    config,
-                    &sig_file,
-                    raw_hashes_file,
-                );
-                events::hashes_verify(id, version, config, v);
-            }
-            else {
-                panic!(
-                    "Could not force sig for index {}. hashes.json is not signed for {id}@{version}.",
-                    config.index
-                );
-            }
-        }
+        let (hashes, _) = self.fetch_hashes(id, version, &info, config)?;
 
         // tar
         eprintln!(
@@ -131,16 +144,84 @@ impl Fetcher {
             err_color_print("Downloading", PossibleColor::BrightYellow),
             &config.target
         );
-        let tar_bytes = self.fetch_blob(
+        let tar_bytes = self.fetch_archive(
             id,
             version,
+            config,
+            &hashes,
             &format!("{}.{}", config.target, info.archive.ext),
+        )?;
+
+        Ok((info, hashes, tar_bytes))
+    }
+
+    /// Download and verify everything needed to install a crate version from a mirror of the index.
+    /// Returns the file names and contents.
+    pub fn mirror(
+        &mut self,
+        id: &str,
+        version: &str,
+        config: &Config,
+    ) -> Result<Vec<(String, Vec<u8>)>, PrebuiltError> {
+        eprintln!(
+            "{} info for {id}@{version}.",
+            err_color_print("Fetching", PossibleColor::BrightBlue),
         );
 
-        // test hashes
-        self.verify_archive(id, version, config, &hashes, &tar_bytes);
+        let (info, info_files) = self.fetch_info(id, version, config)?;
+        let (hashes, hashes_files) = self.fetch_hashes(id, version, &info, config)?;
+
+        let mut files: Vec<(String, Vec<u8>)> = info_files
+            .into_iter()
+            .chain(hashes_files)
+            .map(|(name, raw)| (name, raw.into_bytes()))
+            .collect();
+
+        if info.targets.contains(&config.target) {
+            eprintln!(
+                "{} {id}@{version} for target {}.",
+                err_color_print("Downloading", PossibleColor::BrightYellow),
+                &config.target
+            );
+            let archive = format!("{}.{}", config.target, info.archive.ext);
+            let tar_bytes = self.fetch_archive(id, version, config, &hashes, &archive)?;
+            files.push((archive, tar_bytes));
+        }
+        else {
+            eprintln!(
+                "{id}@{version} does {} target {}, skipping archive.",
+                err_color_print("not support", PossibleColor::BrightYellow),
+                config.target
+            );
+        }
+
+        // Reports are not signed, so they are copied as is.
+        for report in [&info.files.license, &info.files.deps, &info.files.audit] {
+            match self.interact().get_str(id, version, report) {
+                Ok(raw) => files.push((report.clone(), raw.into_bytes())),
+                Err(err) => eprintln!("Could not mirror {report} for {id}@{version}. {err}"),
+            }
+        }
 
-        (info, hashes, tar_bytes)
+        Ok(files)
+    }
+
+    /// Fetch and verify info.json and hashes.json, for checking binaries that are already installed.
+    pub fn hashes(
+        &mut self,
+        id: &str,
+        version: &str,
+        config: &Config,
+    ) -> Result<(InfoFileImm, HashesFileImm), PrebuiltError> {
+        eprintln!(
+            "{} info for {id}@{version}.",
+            err_color_print("Fetching", PossibleColor::BrightBlue),
+        );
+
+        let (info, _) = self.fetch_info(id, version, config)?;
+        let (hashes, _) = self.fetch_hashes(id, version, &info, config)?;
+
+        Ok((info, hashes))
     }
 
     pub fn is_bin(&self, info: &InfoFileImm, bin_name: &str) -> bool {
@@ -148,9 +229,15 @@ impl Fetcher {
         info.bins.contains(&bin_name)
     }
 
-    pub fn reports(&mut self, id: &str, version: &str, info: &InfoFileImm, config: &Config) {
+    pub fn reports(
+        &mut self,
+        id: &str,
+        version: &str,
+        info: &InfoFileImm,
+        config: &Config,
+    ) -> Result<(), PrebuiltError> {
         if config.reports.is_empty() {
-            return;
+            return Ok(());
         }
 
         eprintln!(
@@ -165,7 +252,7 @@ impl Fetcher {
                 ReportType::AuditDL | ReportType::AuditEvent => info.files.audit.clone(),
             };
 
-            let raw_str = &self.fetch_str(id, version, &report_name);
+            let raw_str = &self.fetch_str(id, version, &report_name)?;
 
             match report {
                 ReportType::LicenseDL | ReportType::DepsDL | ReportType::AuditDL => {
@@ -196,53 +283,297 @@ impl Fetcher {
                 ReportType::AuditEvent => events::print_audit(id, version, raw_str),
             }
         }
+
+        Ok(())
     }
 
-    fn fetch_latest(&mut self, id: &str) -> String {
-        match self.interact.get_latest(id) {
-            Ok(s) => s,
-            Err(InteractError::Malformed) => panic!("The version string for {id} is malformed."),
-            Err(InteractError::HttpCode(404)) => panic!(
-                "Crate {id} {} in index!",
-                err_color_print("not found", PossibleColor::BrightRed)
-            ),
-            Err(InteractError::HttpCode(code)) => panic!("Http error {code} for crate {id}."),
-            Err(err) => panic!("Connection error.\n{err}"),
+    /// Fetch and verify info.json, returning it with the raw files that were downloaded.
+    fn fetch_info(
+        &mut self,
+        id: &str,
+        version: &str,
+        config: &Config,
+    ) -> Result<(InfoFileImm, Vec<(String, String)>), PrebuiltError> {
+        // info.json, this picks the index used for the rest of the crate
+        let raw_info_file = self.fetch_first_str(id, version, "info.json")?;
+        events::index(id, version, config, self.index());
+        let info: InfoFile = serde_json::from_str(&raw_info_file).map_err(|_| {
+            PrebuiltError::Malformed(format!("info.json is malformed for {id}@{version}."))
+        })?;
+        let info: InfoFileImm = info.into();
+
+        #[allow(unused_mut)]
+        let mut files = vec![("info.json".to_string(), raw_info_file)];
+
+        // info.json.minisig and test
+        #[cfg(feature = "sig")]
+        if !config.no_verify {
+            if let Some(sig_file) = info.files.sig_info.clone() {
+                let sig = self.fetch_str(id, version, &sig_file)?;
+                let v = self.verify_file(id, version, "info.json", &sig, &files[0].1)?;
+                events::info_verify(id, version, config, v);
+                files.push((sig_file, sig));
+            }
+            else {
+                return Err(PrebuiltError::Signature(format!(
+                    "Could not force sig for index {}. info.json is not signed for {id}@{version}.",
+                    self.index()
+                )));
+            }
         }
+        #[cfg(not(feature = "sig"))]
+        if !config.no_verify {
+            return Err(PrebuiltError::Unsupported(format!("Could not force sig for index {}. This requires the 'security' and/or 'sig' feature(s). Or you can use the flag '--no-verify'.", self.index())));
+        }
+
+        Ok((info, files))
     }
 
-    fn fetch_str(&mut self, id: &str, version: &str, file: &str) -> String {
-        match self.interact.get_str(id, version, file) {
-            Ok(s) => s,
-            Err(InteractError::Malformed) => {
-                panic!("The downloaded string {file} for {id}@{version} is malformed")
+    /// Fetch and verify hashes.json, returning it with the raw files that were downloaded.
+    fn fetch_hashes(
+        &mut self,
+        id: &str,
+        version: &str,
+        info: &InfoFileImm,
+        config: &Config,
+    ) -> Result<(HashesFileImm, Vec<(String, String)>), PrebuiltError> {
+        // hashes.json
+        let raw_hashes_file = self.fetch_str(id, version, &info.files.hash)?;
+        let hashes: HashesFile = serde_json::from_str(&raw_hashes_file).map_err(|_| {
+            PrebuiltError::Malformed(format!(
+                "{} is malformed for {id}@{version}.",
+                info.files.hash
+            ))
+        })?;
+        let hashes: HashesFileImm = hashes.into();
+
+        #[allow(unused_mut)]
+        let mut files = vec![(info.files.hash.clone(), raw_hashes_file)];
+
+        // hashes.json.minisig and test
+        #[cfg(feature = "sig")]
+        if !config.no_verify {
+            if let Some(sig_file) = info.files.sig_hash.clone() {
+                let sig = self.fetch_str(id, version, &sig_file)?;
+                let v = self.verify_file(id, version, &info.files.hash, &sig, &files[0].1)?;
+                events::hashes_verify(id, version, config, v);
+                files.push((sig_file, sig));
             }
-            Err(InteractError::HttpCode(404)) => panic!(
-                "File {file} for {id}@{version} is {}!",
-                err_color_print("not found", PossibleColor::BrightRed)
-            ),
-            Err(InteractError::HttpCode(code)) => {
-                panic!("Http error {code} for {file} for {id}@{version}.")
+            else {
+                return Err(PrebuiltError::Signature(format!(
+                    "Could not force sig for index {}. hashes.json is not signed for {id}@{version}.",
+                    self.index()
+                )));
             }
-            Err(err) => panic!("Connection error.\n{err}"),
         }
+
+        Ok((hashes, files))
+    }
+
+    fn interact(&mut self) -> &mut dyn Interact {
+        self.indexes[self.current].1.as_mut()
+    }
+
+    /// Agent shared by every interact, to create more fetchers from.
+    pub fn agent(&self) -> Agent {
+        self.agent.clone()
+    }
+
+    /// Position of the index serving the current crate, to switch back to it later.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn set_current(&mut self, current: usize) {
+        self.current = current;
+    }
+
+    /// Index serving the current crate.
+    pub fn index(&self) -> &str {
+        &self.indexes[self.current].0.index
+    }
+
+    fn sigs(&self) -> &HashSet<String> {
+        &self.indexes[self.current].0.sigs
     }
 
-    fn fetch_blob(&mut self, id: &str, version: &str, file: &str) -> Vec<u8> {
-        match self.interact.get_blob(id, version, file) {
-            Ok(s) => s,
-            Err(InteractError::Malformed) => {
-                panic!("The downloaded blob {file} for {id}@{version} is malformed")
+    /// Indexes to try for a crate. Routed crates only use their index, so they cannot be shadowed.
+    fn candidates(&self, id: &str) -> Vec<usize> {
+        let route = self
+            .routes
+            .iter()
+            .find(|(pattern, _)| pattern.eq(id))
+            .or_else(|| {
+                self.routes
+                    .iter()
+                    .find(|(pattern, _)| config::route_matches(pattern, id))
+            });
+
+        match route {
+            Some((_, i)) => vec![*i],
+            None => self.chain.clone(),
+        }
+    }
+
+    /// Ids from every index that supports listing crates.
+    fn fetch_ids(&mut self) -> Result<Vec<String>, PrebuiltError> {
+        let mut ids = IndexSet::new();
+        let mut supported = false;
+        for i in self.chain.clone() {
+            self.current = i;
+            match self.interact().get_ids() {
+                Ok(i) => {
+                    supported = true;
+                    ids.extend(i);
+                }
+                Err(InteractError::Unsupported) => eprintln!(
+                    "Index {} {} listing crates, skipping it.",
+                    self.index(),
+                    err_color_print("does not support", PossibleColor::BrightYellow),
+                ),
+                Err(err) => {
+                    return Err(PrebuiltError::from_interact(
+                        err,
+                        "stable index",
+                        self.index(),
+                    ))
+                }
             }
-            Err(InteractError::HttpCode(404)) => panic!(
-                "File {file} for {id}@{version} is {}!",
-                err_color_print("not found", PossibleColor::BrightRed)
-            ),
-            Err(InteractError::HttpCode(code)) => {
-                panic!("Http error {code} for {file} for {id}@{version}.")
+        }
+
+        if !supported {
+            return Err(PrebuiltError::Unsupported(
+                "No index supports listing crates, please pass them as PKGS.".to_string(),
+            ));
+        }
+
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Latest version from the first index that has the crate.
+    fn fetch_latest(&mut self, id: &str) -> Result<String, PrebuiltError> {
+        for i in self.candidates(id) {
+            self.current = i;
+            match self.interact().get_latest(id) {
+                Ok(s) => return Ok(s),
+                Err(InteractError::HttpCode(404)) => eprintln!(
+                    "Crate {id} {} in index {}.",
+                    err_color_print("not found", PossibleColor::BrightYellow),
+                    self.index()
+                ),
+                Err(err) => {
+                    return Err(PrebuiltError::from_interact(
+                        err,
+                        &format!("Latest version of {id}"),
+                        self.index(),
+                    ))
+                }
             }
-            Err(err) => panic!("Connection error.\n{err}"),
         }
+
+        Err(PrebuiltError::NotFound(format!(
+            "Crate {id} was not found in any index."
+        )))
+    }
+
+    /// Fetch a file from the first index that has it, and keep using that index.
+    fn fetch_first_str(
+        &mut self,
+        id: &str,
+        version: &str,
+        file: &str,
+    ) -> Result<String, PrebuiltError> {
+        let candidates = self.candidates(id);
+        for (n, i) in candidates.iter().enumerate() {
+            self.current = *i;
+            match self.interact().get_str(id, version, file) {
+                Err(InteractError::HttpCode(404)) if n + 1 < candidates.len() => eprintln!(
+                    "File {file} for {id}@{version} {} in index {}, trying the next index.",
+                    err_color_print("not found", PossibleColor::BrightYellow),
+                    self.index()
+                ),
+                res => return self.map_err(id, version, file, res),
+            }
+        }
+
+        Err(PrebuiltError::Config(format!(
+            "No indexes to fetch {file} for {id}@{version} from."
+        )))
+    }
+
+    fn fetch_str(&mut self, id: &str, version: &str, file: &str) -> Result<String, PrebuiltError> {
+        let res = self.interact().get_str(id, version, file);
+        self.map_err(id, version, file, res)
+    }
+
+    fn map_err<T>(
+        &self,
+        id: &str,
+        version: &str,
+        file: &str,
+        res: Result<T, InteractError>,
+    ) -> Result<T, PrebuiltError> {
+        res.map_err(|err| {
+            PrebuiltError::from_interact(
+                err,
+                &format!("File {file} for {id}@{version}"),
+                self.index(),
+            )
+        })
+    }
+
+    /// Fetch and verify an archive, using the download cache if possible.
+    fn fetch_archive(
+        &mut self,
+        id: &str,
+        version: &str,
+        config: &Config,
+        hashes: &HashesFileImm,
+        archive: &str,
+    ) -> Result<Vec<u8>, PrebuiltError> {
+        let archive_hashes = target_hashes(
+            id,
+            version,
+            &config.target,
+            config.allow_missing_hashes,
+            hashes,
+        )?
+        .map(|blob| &blob.archive);
+
+        if let Some(bytes) = self
+            .cache
+            .as_ref()
+            .zip(archive_hashes)
+            .and_then(|(cache, h)| cache.get(h))
+        {
+            eprintln!(
+                "{} cached {id}@{version} for target {}.",
+                err_color_print("Using", PossibleColor::BrightYellow),
+                &config.target
+            );
+            self.verify_archive(id, version, config, hashes, &bytes)?;
+            return Ok(bytes);
+        }
+
+        let bytes = self.fetch_blob(id, version, archive)?;
+        self.verify_archive(id, version, config, hashes, &bytes)?;
+
+        // Only added after being verified.
+        if let Some((cache, h)) = self.cache.as_ref().zip(archive_hashes) {
+            cache.put(h, &bytes);
+        }
+
+        Ok(bytes)
+    }
+
+    fn fetch_blob(
+        &mut self,
+        id: &str,
+        version: &str,
+        file: &str,
+    ) -> Result<Vec<u8>, PrebuiltError> {
+        let res = self.interact().get_blob(id, version, file);
+        self.map_err(id, version, file, res)
     }
 
     #[cfg(feature = "sig")]
@@ -251,26 +582,28 @@ impl Fetcher {
         id: &str,
         version: &str,
         file: &str,
-        config: &Config,
-        sig_file: &str,
+        sig: &str,
         raw_file: &str,
-    ) -> bool {
+    ) -> Result<bool, PrebuiltError> {
         use minisign_verify::{PublicKey, Signature};
 
-        if config.sigs.is_empty() {
-            panic!(
-                "{} for index '{}'. Please add one with --pub-key or use --no-verify.",
-                err_color_print("No public key(s)", PossibleColor::BrightRed),
-                config.index
-            );
+        if self.sigs().is_empty() {
+            return Err(PrebuiltError::Signature(format!(
+                "No public key(s) for index '{}'. Please add one with --pub-key or use --no-verify.",
+                self.index()
+            )));
         }
 
-        let sig = &self.fetch_str(id, version, sig_file);
-        let signature = Signature::decode(sig).expect("Signature was malformed.");
+        let signature = Signature::decode(sig).map_err(|_| {
+            PrebuiltError::Signature(format!(
+                "Signature for {file} for {id}@{version} was malformed."
+            ))
+        })?;
 
         let mut verified = false;
-        for key in config.sigs.iter() {
-            let pk = PublicKey::from_base64(key).expect("Public key was malformed.");
+        for key in self.sigs().iter() {
+            let pk = PublicKey::from_base64(key)
+                .map_err(|_| PrebuiltError::Config(format!("Public key {key} was malformed.")))?;
             if pk.verify(raw_file.as_bytes(), &signature, false).is_ok() {
                 verified = true;
                 break;
@@ -278,10 +611,9 @@ impl Fetcher {
         }
 
         if !verified {
-            panic!(
-                "{} verify {file} for {id}@{version}.",
-                err_color_print("Could not", PossibleColor::BrightRed)
-            );
+            return Err(PrebuiltError::Signature(format!(
+                "Could not verify {file} for {id}@{version}."
+            )));
         }
         else {
             eprintln!(
@@ -290,7 +622,7 @@ impl Fetcher {
             );
         }
 
-        verified
+        Ok(verified)
     }
 
     fn verify_archive(
@@ -300,133 +632,225 @@ impl Fetcher {
         config: &Config,
         hashes: &HashesFileImm,
         bytes: &[u8],
-    ) {
-        if let Some(blob) = hashes.hashes.get(&config.target) {
-            let hashes = &blob.archive;
-            self.verify_bytes(
+    ) -> Result<(), PrebuiltError> {
+        match target_hashes(
+            id,
+            version,
+            &config.target,
+            config.allow_missing_hashes,
+            hashes,
+        )? {
+            Some(blob) => self.verify_bytes(
                 id,
                 version,
-                hashes,
+                config,
+                &blob.archive,
                 &format!("{} archive", &config.target),
                 bytes,
-            )
-        }
-    }
-
-    fn verify_bytes(&self, id: &str, version: &str, hashes: &Hashes, item: &str, bytes: &[u8]) {
-        #[cfg(feature = "sha3")]
-        {
-            use sha3::{Digest, Sha3_256, Sha3_512};
-
-            // sha3_512
-            if let Some(sha_hash) = hashes.get(&HashType::Sha3_512) {
-                let mut hasher = Sha3_512::new();
-                hasher.update(bytes);
-                let hash: Vec<u8> = hasher.finalize().to_vec();
-                let hash = hex::encode(hash);
-
-                if !hash.eq(sha_hash) {
-                    panic!("sha3_512 hashes do not match for {item}. {sha_hash} != {hash}");
-                }
-
+            ),
+            None => {
                 eprintln!(
-                    "{} {item} for {id}@{version} with sha3_512.",
-                    err_color_print("Verified", PossibleColor::BrightWhite)
+                    "{} {id}@{version} has no hashes for target {}, installing without verifying it.",
+                    err_color_print("Warning:", PossibleColor::BrightYellow),
+                    config.target
                 );
-                return;
+                Ok(())
             }
+        }
+    }
 
-            // sha3_256
-            if let Some(sha_hash) = hashes.get(&HashType::Sha3_256) {
-                let mut hasher = Sha3_256::new();
-                hasher.update(bytes);
-                let hash: Vec<u8> = hasher.finalize().to_vec();
-                let hash = hex::encode(hash);
+    /// Verify a binary against its own hashes, binaries without hashes are never trusted.
+    pub fn verify_bin(
+        &self,
+        id: &str,
+        version: &str,
+        config: &Config,
+        hashes: &HashesFileImm,
+        bin_name: &str,
+        bytes: &[u8],
+    ) -> Result<(), PrebuiltError> {
+        // Only missing if --allow-missing-hashes was used.
+        let Some(blob) = target_hashes(
+            id,
+            version,
+            &config.target,
+            config.allow_missing_hashes,
+            hashes,
+        )?
+        else {
+            return Ok(());
+        };
 
-                if !hash.eq(sha_hash) {
-                    panic!("sha3_256 hashes do not match for {item}. {sha_hash} != {hash}");
-                }
+        let bin_hashes = blob
+            .bins
+            .get(bin_name)
+            .or_else(|| blob.bins.get(&bin_name.replace(".exe", "")));
 
-                eprintln!(
-                    "{} {item} for {id}@{version} with sha3_256.",
-                    err_color_print("Verified", PossibleColor::BrightWhite)
-                );
-                return;
-            }
+        match bin_hashes {
+            Some(bin_hashes) => self.verify_bytes(
+                id,
+                version,
+                config,
+                bin_hashes,
+                &format!("{bin_name} binary"),
+                bytes,
+            ),
+            None => Err(PrebuiltError::HashMismatch(format!(
+                "Could not find {bin_name} hash for {id}@{version} with target {}.",
+                config.target
+            ))),
         }
+    }
 
-        #[cfg(feature = "sha2")]
+    fn verify_bytes(
+        &self,
+        id: &str,
+        version: &str,
+        config: &Config,
+        hashes: &Hashes,
+        item: &str,
+        bytes: &[u8],
+    ) -> Result<(), PrebuiltError> {
+        #[cfg(not(any(feature = "sha2", feature = "sha3")))]
         {
-            use sha2::{Digest, Sha256, Sha512};
+            eprintln!("Could not verify downloaded {item} for {id}@{version}. This requires the 'security', 'sha3', and/or 'sha2' feature(s).");
+            Ok(())
+        }
 
-            // sha512
-            if let Some(sha_hash) = hashes.get(&HashType::Sha512) {
-                let mut hasher = Sha512::new();
-                hasher.update(bytes);
-                let hash: Vec<u8> = hasher.finalize().to_vec();
-                let hash = hex::encode(hash);
+        #[cfg(any(feature = "sha2", feature = "sha3"))]
+        {
+            let mut verified = false;
+            for hash_type in config.hashes.iter() {
+                let name: &str = hash_type.into();
+                let Some(sha_hash) = hashes.get(hash_type)
+                else {
+                    if config.all_hashes {
+                        return Err(PrebuiltError::HashMismatch(format!(
+                            "Could not verify downloaded {item} for {id}@{version}, missing {name} hash."
+                        )));
+                    }
+                    continue;
+                };
 
+                let hash = hash_type.digest(bytes);
                 if !hash.eq(sha_hash) {
-                    panic!("sha512 hashes do not match for {item}. {sha_hash} != {hash}");
+                    return Err(PrebuiltError::HashMismatch(format!(
+                        "{name} hashes do not match for {item}. {sha_hash} != {hash}"
+                    )));
                 }
 
                 eprintln!(
-                    "{} {item} for {id}@{version} with sha512.",
+                    "{} {item} for {id}@{version} with {name}.",
                     err_color_print("Verified", PossibleColor::BrightWhite)
                 );
-                return;
-            }
-
-            // sha256
-            if let Some(sha_hash) = hashes.get(&HashType::Sha256) {
-                let mut hasher = Sha256::new();
-                hasher.update(bytes);
-                let hash: Vec<u8> = hasher.finalize().to_vec();
-                let hash = hex::encode(hash);
+                verified = true;
 
-                if !hash.eq(sha_hash) {
-                    panic!("sha256 hashes do not match for {item}. {sha_hash} != {hash}");
+                if !config.all_hashes {
+                    break;
                 }
+            }
 
-                eprintln!(
-                    "{} {item} for {id}@{version} with sha256.",
-                    err_color_print("Verified", PossibleColor::BrightWhite)
-                );
-                return;
+            if !verified {
+                return Err(PrebuiltError::HashMismatch(format!(
+                    "Could not verify downloaded {item} for {id}@{version}, no allowed hashes."
+                )));
             }
+
+            Ok(())
         }
+    }
+}
 
-        #[cfg(not(any(feature = "sha2", feature = "sha3")))]
-        eprintln!("Could not verify downloaded {item} for {id}@{version}. This requires the 'security', 'sha3', and/or 'sha2' feature(s).");
+/// Hashes for the target, failing closed if there are none unless --allow-missing-hashes is used.
+fn target_hashes<'a>(
+    id: &str,
+    version: &str,
+    target: &str,
+    allow_missing: bool,
+    hashes: &'a HashesFileImm,
+) -> Result<Option<&'a HashesFileBlobV1>, PrebuiltError> {
+    match hashes.hashes.get(target) {
+        Some(blob) => Ok(Some(blob)),
+        None if allow_missing => Ok(None),
+        None => Err(PrebuiltError::HashMismatch(format!(
+            "No hashes for target {target} in hashes.json for {id}@{version}. (Use --allow-missing-hashes to install it anyway)"
+        ))),
+    }
+}
 
-        #[cfg(any(feature = "sha2", feature = "sha3"))]
-        panic!("Could not verify downloaded {item} for {id}@{version}.");
-    }
-
-    // TODO: Use for update hashing.
-    // No need to verify bins, since archive hash should do this for us.
-    //    pub fn verify_bin(&self, config: &Config, bin_name: &str, bytes: &[u8]) {
-    //        let id = self
-    //            .data
-    //            .id
-    //            .as_ref()
-    //            .expect("Failed to get id, but it should have been guaranteed.");
-    //        let version = self
-    //            .data
-    //            .version
-    //            .as_ref()
-    //            .expect("Failed to get version, but it should have been guaranteed.");
-    //        let hashes = self
-    //            .data
-    //            .hashes
-    //            .as_ref()
-    //            .expect("Failed to get hashes, but it should have been guaranteed.");
-    //
-    //        if let Some(blob) = hashes.hashes.get(&config.target) {
-    //            match &blob.bins.get(bin_name) {
-    //                Some(blob) => self.verify_bytes(blob, &format!("{bin_name} binary"), bytes),
-    //                None => panic!("Could not find {bin_name} hash for {id}@{version}."),
-    //            }
-    //        }
-    //    }
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, fs};
+
+    use super::{target_hashes, Fetcher};
+    use crate::{
+        config::{test_config, IndexConfig},
+        create_agent,
+        data::{ConfigFileHttp, HashesFile, HashesFileImm},
+        error::PrebuiltError,
+        test::index_crate,
+    };
+
+    #[test]
+    fn test_missing_target_hashes() {
+        let json = include_str!("../test/hashes_3.json");
+        let hashes: HashesFile = serde_json::from_str(json).unwrap();
+        let hashes: HashesFileImm = hashes.into();
+
+        let target = "x86_64-unknown-linux-gnu";
+        assert!(matches!(
+            target_hashes("demo", "1.0.0", target, false, &hashes),
+            Err(PrebuiltError::HashMismatch(_))
+        ));
+        assert!(matches!(
+            target_hashes("demo", "1.0.0", target, true, &hashes),
+            Ok(None)
+        ));
+
+        let target = "aarch64-unknown-linux-gnu";
+        assert!(matches!(
+            target_hashes("demo", "1.0.0", target, false, &hashes),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn test_candidates() {
+        let root = std::env::temp_dir().join(format!("prebuilt-candidates-{}", std::process::id()));
+        let (public, internal) = (root.join("public"), root.join("internal"));
+        let index = format!("--index=file:{}", public.display());
+        let mut config = test_config(&root, &[&index, "demo"]);
+        index_crate(
+            &public,
+            &config.target,
+            "acme-tool",
+            "9.9.9",
+            b"shadow",
+            None,
+        );
+        index_crate(&public, &config.target, "ripgrep", "14.1.0", b"rg", None);
+        fs::create_dir_all(&internal).unwrap();
+        config.routes.insert(
+            "acme-*".to_string(),
+            IndexConfig {
+                index: format!("file:{}", internal.display()),
+                auth: None,
+                http: ConfigFileHttp::default(),
+                sigs: HashSet::new(),
+            },
+        );
+
+        let mut fetcher = Fetcher::new(&config, create_agent().unwrap()).unwrap();
+        assert_eq!(fetcher.candidates("acme-tool"), vec![1]);
+        assert_eq!(fetcher.candidates("ripgrep"), vec![0]);
+
+        // A routed crate missing from its index never falls back to another one
+        assert!(matches!(
+            fetcher.get_latest("acme-tool"),
+            Err(PrebuiltError::NotFound(_))
+        ));
+        assert_eq!(fetcher.get_latest("ripgrep").unwrap(), "14.1.0");
+
+        fs::remove_dir_all(root).unwrap();
+    }
 }
@@ -0,0 +1,195 @@
+use crate::{
+    data::ConfigFileHttp,
+    interact::{Interact, InteractError},
+};
+use ureq::{Agent, Error};
+
+static DEFAULT_LATEST: &str = "{base}/stable-index/{id}";
+static DEFAULT_FILE: &str = "{base}/{id}-{version}/{file}";
+static DEFAULT_AUTH_HEADER: &str = "Authorization";
+
+/// Index served from any static file server, located by url templates.
+pub struct CustomHttp {
+    agent: Agent,
+    auth: Option<(String, String)>,
+    base: String,
+    latest: String,
+    file: String,
+}
+impl CustomHttp {
+    pub fn new(
+        agent: Agent,
+        auth_token: Option<String>,
+        http: &ConfigFileHttp,
+        slug: &str,
+    ) -> Self {
+        Self::with_url(agent, auth_token, http, &format!("https://{slug}"))
+    }
+
+    fn with_url(
+        agent: Agent,
+        auth_token: Option<String>,
+        http: &ConfigFileHttp,
+        url: &str,
+    ) -> Self {
+        // Without a custom header the token is sent as a bearer token.
+        let auth = auth_token.map(|token| match &http.auth_header {
+            Some(header) => (header.clone(), token),
+            None => (DEFAULT_AUTH_HEADER.to_string(), format!("Bearer {token}")),
+        });
+
+        Self {
+            agent,
+            auth,
+            base: url.trim_end_matches('/').to_string(),
+            latest: http
+                .latest
+                .clone()
+                .unwrap_or_else(|| DEFAULT_LATEST.to_string()),
+            file: http
+                .file
+                .clone()
+                .unwrap_or_else(|| DEFAULT_FILE.to_string()),
+        }
+    }
+
+    fn url(&self, template: &str, id: &str, version: &str, file: &str) -> String {
+        template
+            .replace("{base}", &self.base)
+            .replace("{id}", id)
+            .replace("{version}", version)
+            .replace("{file}", file)
+    }
+
+    fn call(&self, url: &str) -> Result<Vec<u8>, InteractError> {
+        let mut req = self.agent.get(url);
+        if let Some((header, value)) = &self.auth {
+            req = req.set(header, value);
+        }
+
+        match req.call() {
+            Ok(response) => {
+                let mut bytes = Vec::new();
+                //TODO: Allow limiting of size.
+                response
+                    .into_reader()
+                    .read_to_end(&mut bytes)
+                    .map_err(|_| InteractError::Malformed)?;
+                Ok(bytes)
+            }
+            Err(Error::Status(401 | 403, _)) if self.auth.is_some() => {
+                Err(InteractError::Unauthorized)
+            }
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn call_str(&self, url: &str) -> Result<String, InteractError> {
+        let bytes = self.call(url)?;
+        let s = String::from_utf8(bytes).map_err(|_| InteractError::Malformed)?;
+        Ok(s.trim().to_string())
+    }
+}
+impl Interact for CustomHttp {
+    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+        let url = self.url(&self.latest, id, "", "");
+        self.call_str(&url)
+    }
+
+    fn get_str(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<String, InteractError> {
+        let url = self.url(&self.file, id, version, file_name);
+        self.call_str(&url)
+    }
+
+    fn get_blob(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<Vec<u8>, InteractError> {
+        let url = self.url(&self.file, id, version, file_name);
+        self.call(&url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CustomHttp;
+    use crate::{
+        data::ConfigFileHttp,
+        interact::{test_server::TestServer, Interact, InteractError},
+    };
+
+    #[test]
+    fn test_custom_http_default() {
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route("/index/stable-index/ripgrep", 200, "14.1.0\n");
+        server.route("/index/ripgrep-14.1.0/info.json", 200, "{}");
+        let requests = server.start();
+
+        let mut http = CustomHttp::with_url(
+            ureq::agent(),
+            None,
+            &ConfigFileHttp::default(),
+            &format!("{url}/index/"),
+        );
+        assert_eq!(http.get_latest("ripgrep").unwrap(), "14.1.0");
+        assert_eq!(
+            http.get_str("ripgrep", "14.1.0", "info.json").unwrap(),
+            "{}"
+        );
+        assert!(matches!(
+            http.get_str("ripgrep", "14.1.0", "hashes.json"),
+            Err(InteractError::HttpCode(404))
+        ));
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| !r.headers.contains_key("authorization")));
+    }
+
+    #[test]
+    fn test_custom_http_templates() {
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route("/latest/ripgrep", 200, "14.1.0");
+        server.route(
+            "/ripgrep/14.1.0/x86_64-unknown-linux-gnu.tar.gz",
+            200,
+            vec![0, 1, 2],
+        );
+        server.route("/ripgrep/0.0.1/info.json", 401, "");
+        let requests = server.start();
+
+        let templates = ConfigFileHttp {
+            latest: Some("{base}/latest/{id}".to_string()),
+            file: Some("{base}/{id}/{version}/{file}".to_string()),
+            auth_header: Some("X-Api-Key".to_string()),
+        };
+        let mut http =
+            CustomHttp::with_url(ureq::agent(), Some("secret".to_string()), &templates, &url);
+        assert_eq!(http.get_latest("ripgrep").unwrap(), "14.1.0");
+        assert_eq!(
+            http.get_blob("ripgrep", "14.1.0", "x86_64-unknown-linux-gnu.tar.gz")
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(matches!(
+            http.get_str("ripgrep", "0.0.1", "info.json"),
+            Err(InteractError::Unauthorized)
+        ));
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| r.headers.get("x-api-key").map(|t| t.as_str()) == Some("secret")));
+    }
+}
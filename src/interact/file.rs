@@ -0,0 +1,139 @@
+use crate::{
+    error::PrebuiltError,
+    interact::{Interact, InteractError},
+};
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+/// Index stored in a directory, laid out like the releases of a remote index.
+///
+/// ```text
+/// $ROOT/stable-index/$ID
+/// $ROOT/$ID-$VERSION/$FILE
+/// ```
+pub struct FileIndex {
+    root: PathBuf,
+}
+impl FileIndex {
+    pub fn new(root: &str) -> Result<Self, PrebuiltError> {
+        let root = PathBuf::from(root);
+        if !root.is_dir() {
+            return Err(PrebuiltError::Config(format!(
+                "Index directory {root:?} does not exist."
+            )));
+        }
+
+        Ok(Self { root })
+    }
+
+    fn path(&self, dir: &str, file: &str) -> Result<PathBuf, InteractError> {
+        // Names come from the index, so do not let them escape the index directory.
+        for name in [dir, file] {
+            if name.is_empty() || name.eq("..") || name.contains(['/', '\\']) {
+                return Err(InteractError::Malformed);
+            }
+        }
+
+        let mut path = self.root.clone();
+        path.push(dir);
+        path.push(file);
+        Ok(path)
+    }
+
+    fn read(&self, dir: &str, file: &str) -> Result<Vec<u8>, InteractError> {
+        match fs::read(self.path(dir, file)?) {
+            Ok(bytes) => Ok(bytes),
+            // Missing files are reported like a missing file on a remote index.
+            Err(err) if err.kind() == ErrorKind::NotFound => Err(InteractError::HttpCode(404)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn read_str(&self, dir: &str, file: &str) -> Result<String, InteractError> {
+        let bytes = self.read(dir, file)?;
+        let s = String::from_utf8(bytes).map_err(|_| InteractError::Malformed)?;
+        Ok(s.trim().to_string())
+    }
+}
+impl Interact for FileIndex {
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        let mut dir = self.root.clone();
+        dir.push("stable-index");
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|_| InteractError::HttpCode(404))? {
+            let entry = entry.map_err(|_| InteractError::ConnectionError)?;
+            let id = entry
+                .file_name()
+                .into_string()
+                .map_err(|_| InteractError::Malformed)?;
+            ids.push(id);
+        }
+        ids.sort();
+
+        Ok(ids)
+    }
+
+    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+        self.read_str("stable-index", id)
+    }
+
+    fn get_str(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<String, InteractError> {
+        self.read_str(&format!("{id}-{version}"), file_name)
+    }
+
+    fn get_blob(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<Vec<u8>, InteractError> {
+        self.read(&format!("{id}-{version}"), file_name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileIndex;
+    use crate::interact::{Interact, InteractError};
+    use std::fs;
+
+    #[test]
+    fn test_file_index() {
+        let mut root = std::env::temp_dir();
+        root.push(format!("cargo-prebuilt-file-index-{}", std::process::id()));
+        fs::create_dir_all(root.join("stable-index")).unwrap();
+        fs::create_dir_all(root.join("ripgrep-14.1.0")).unwrap();
+        fs::write(root.join("stable-index/ripgrep"), "14.1.0\n").unwrap();
+        fs::write(root.join("ripgrep-14.1.0/info.json"), "{}").unwrap();
+        fs::write(root.join("ripgrep-14.1.0/target.tar.gz"), [0, 1, 2]).unwrap();
+
+        let mut index = FileIndex::new(root.to_str().unwrap()).unwrap();
+        assert_eq!(index.get_ids().unwrap(), vec!["ripgrep".to_string()]);
+        assert_eq!(index.get_latest("ripgrep").unwrap(), "14.1.0");
+        assert_eq!(
+            index.get_str("ripgrep", "14.1.0", "info.json").unwrap(),
+            "{}"
+        );
+        assert_eq!(
+            index
+                .get_blob("ripgrep", "14.1.0", "target.tar.gz")
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(matches!(
+            index.get_latest("bat"),
+            Err(InteractError::HttpCode(404))
+        ));
+        assert!(matches!(
+            index.get_str("ripgrep", "14.1.0", "../stable-index/ripgrep"),
+            Err(InteractError::Malformed)
+        ));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{
+    error::PrebuiltError,
+    interact::{Interact, InteractError},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::HashMap;
+use ureq::{Agent, Error, Request};
+
+#[derive(Clone, Debug, Deserialize)]
+struct Release {
+    assets: Vec<ReleaseAssets>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ReleaseAssets {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Forgejo (and Gitea) index, public if there is no auth token.
+pub struct Forgejo {
+    agent: Agent,
+    auth_token: Option<String>,
+    origin: String, // Only urls on this origin are sent the auth token
+    u_url: String,
+    stable_index: Option<Release>,
+    index: HashMap<String, Release>,
+}
+impl Forgejo {
+    pub fn new(
+        agent: Agent,
+        auth_token: Option<String>,
+        slug: &str,
+    ) -> Result<Self, PrebuiltError> {
+        let s: Vec<&str> = slug.split('/').collect();
+        if s.len() != 3 {
+            return Err(PrebuiltError::Config(format!(
+                "Slug '{slug}' is not formatted properly."
+            )));
+        }
+
+        Ok(Self::with_url(
+            agent,
+            auth_token,
+            &format!("https://{}", s[0]),
+            s[1],
+            s[2],
+        ))
+    }
+
+    fn with_url(
+        agent: Agent,
+        auth_token: Option<String>,
+        url: &str,
+        owner: &str,
+        repo: &str,
+    ) -> Self {
+        Self {
+            agent,
+            auth_token,
+            origin: url.trim_end_matches('/').to_string(),
+            u_url: format!("{url}/api/v1/repos/{owner}/{repo}/releases/tags"),
+            stable_index: None,
+            index: HashMap::new(),
+        }
+    }
+
+    fn request(&self, url: &str) -> Request {
+        let req = self.agent.get(url);
+
+        // Release assets can point anywhere, so never hand the token to another host.
+        let same_origin = url
+            .strip_prefix(&self.origin)
+            .is_some_and(|path| path.starts_with('/'));
+        match &self.auth_token {
+            Some(token) if same_origin => {
+                req.set("Authorization", format!("token {token}").as_str())
+            }
+            _ => req,
+        }
+    }
+
+    fn api_call<T: DeserializeOwned>(&self, url: &str) -> Result<T, InteractError> {
+        match self.request(url).set("Accept", "application/json").call() {
+            Ok(res) => {
+                let s = res.into_string().map_err(|_| InteractError::Malformed)?;
+                serde_json::from_str(&s).map_err(|_| InteractError::Malformed)
+            }
+            Err(Error::Status(401 | 403, _)) if self.auth_token.is_some() => {
+                Err(InteractError::Unauthorized)
+            }
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn call(&self, url: &str) -> Result<Vec<u8>, InteractError> {
+        match self.request(url).call() {
+            Ok(response) => {
+                let mut bytes = Vec::new();
+                //TODO: Allow limiting of size.
+                response
+                    .into_reader()
+                    .read_to_end(&mut bytes)
+                    .map_err(|_| InteractError::Malformed)?;
+                Ok(bytes)
+            }
+            Err(Error::Status(401 | 403, _)) if self.auth_token.is_some() => {
+                Err(InteractError::Unauthorized)
+            }
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn get_file(&self, release: &Release, file: &str) -> Result<Vec<u8>, InteractError> {
+        match release.assets.iter().find(|a| a.name.eq(file)) {
+            Some(asset) => self.call(&asset.browser_download_url),
+            None => Err(InteractError::HttpCode(404)),
+        }
+    }
+
+    fn get_str_file(&self, release: &Release, file: &str) -> Result<String, InteractError> {
+        let bytes = self.get_file(release, file)?;
+        let s = String::from_utf8(bytes).map_err(|_| InteractError::Malformed)?;
+        Ok(s.trim().to_string())
+    }
+
+    fn get_release(&mut self, id: &str, version: &str) -> Result<Release, InteractError> {
+        let key = format!("{id}/--/{version}");
+        match self.index.get(&key) {
+            Some(item) => Ok(item.clone()),
+            None => {
+                let rel = self.api_call(&format!("{}/{id}-{version}", self.u_url))?;
+                let _ = self.index.insert(key.clone(), rel);
+                Ok(self.index.get(&key).unwrap().clone())
+            }
+        }
+    }
+
+    fn get_stable_index(&mut self) -> Result<&Release, InteractError> {
+        if self.stable_index.is_none() {
+            self.stable_index = Some(self.api_call(&format!("{}/stable-index", self.u_url))?)
+        }
+
+        Ok(self
+            .stable_index
+            .as_ref()
+            .expect("Should have stable index!"))
+    }
+}
+impl Interact for Forgejo {
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        let si = self.get_stable_index()?;
+        Ok(si.assets.iter().map(|a| a.name.clone()).collect())
+    }
+
+    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+        self.get_stable_index()?;
+
+        // Get latest from file
+        let si = self
+            .stable_index
+            .as_ref()
+            .expect("Should have stable index!");
+
+        self.get_str_file(si, id)
+    }
+
+    fn get_str(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<String, InteractError> {
+        let release = self.get_release(id, version)?;
+        self.get_str_file(&release, file_name)
+    }
+
+    fn get_blob(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<Vec<u8>, InteractError> {
+        let release = self.get_release(id, version)?;
+        self.get_file(&release, file_name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Forgejo;
+    use crate::interact::{test_server::TestServer, Interact, InteractError};
+
+    fn release(url: &str, files: &[&str]) -> String {
+        let assets: Vec<String> = files
+            .iter()
+            .map(|f| {
+                format!(
+                    r#"{{"id":1,"name":"{f}","size":1,"download_count":0,"uuid":"{f}","browser_download_url":"{url}/attachments/{f}"}}"#
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"id":1,"tag_name":"t","draft":false,"prerelease":false,"assets":[{}]}}"#,
+            assets.join(",")
+        )
+    }
+
+    fn server(code: u16) -> (TestServer, String) {
+        let mut server = TestServer::new();
+        let url = server.url();
+        let api = "/api/v1/repos/owner/index/releases/tags";
+        server.route(
+            &format!("{api}/stable-index"),
+            code,
+            release(&url, &["ripgrep"]),
+        );
+        server.route("/attachments/ripgrep", code, "14.1.0\n");
+        server.route(
+            &format!("{api}/ripgrep-14.1.0"),
+            code,
+            release(&url, &["info.json", "x86_64-unknown-linux-gnu.tar.gz"]),
+        );
+        server.route("/attachments/info.json", code, "{}");
+        server.route(
+            "/attachments/x86_64-unknown-linux-gnu.tar.gz",
+            code,
+            vec![0, 1, 2],
+        );
+        (server, url)
+    }
+
+    #[test]
+    fn test_forgejo_public() {
+        let (server, url) = server(200);
+        let requests = server.start();
+
+        let mut fj = Forgejo::with_url(ureq::agent(), None, &url, "owner", "index");
+        assert_eq!(fj.get_ids().unwrap(), vec!["ripgrep".to_string()]);
+        assert_eq!(fj.get_latest("ripgrep").unwrap(), "14.1.0");
+        assert_eq!(fj.get_str("ripgrep", "14.1.0", "info.json").unwrap(), "{}");
+        assert_eq!(
+            fj.get_blob("ripgrep", "14.1.0", "x86_64-unknown-linux-gnu.tar.gz")
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(matches!(
+            fj.get_str("ripgrep", "14.1.0", "hashes.json"),
+            Err(InteractError::HttpCode(404))
+        ));
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| !r.headers.contains_key("authorization")));
+    }
+
+    #[test]
+    fn test_forgejo_private() {
+        let (server, url) = server(200);
+        let requests = server.start();
+
+        let mut fj = Forgejo::with_url(
+            ureq::agent(),
+            Some("secret".to_string()),
+            &url,
+            "owner",
+            "index",
+        );
+        assert_eq!(fj.get_str("ripgrep", "14.1.0", "info.json").unwrap(), "{}");
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| r.headers.get("authorization").map(|t| t.as_str()) == Some("token secret")));
+    }
+
+    #[test]
+    fn test_forgejo_private_other_host() {
+        // Assets on a second server, which must never see the token.
+        let mut other = TestServer::new();
+        let other_url = other.url();
+        other.route("/attachments/info.json", 200, "{}");
+        let other_requests = other.start();
+
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route(
+            "/api/v1/repos/owner/index/releases/tags/ripgrep-14.1.0",
+            200,
+            release(&other_url, &["info.json"]),
+        );
+        let requests = server.start();
+
+        let mut fj = Forgejo::with_url(
+            ureq::agent(),
+            Some("secret".to_string()),
+            &url,
+            "owner",
+            "index",
+        );
+        assert_eq!(fj.get_str("ripgrep", "14.1.0", "info.json").unwrap(), "{}");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].headers.contains_key("authorization"));
+        let other_requests = other_requests.lock().unwrap();
+        assert_eq!(other_requests.len(), 1);
+        assert!(!other_requests[0].headers.contains_key("authorization"));
+    }
+
+    #[test]
+    fn test_forgejo_bad_token() {
+        let (server, url) = server(401);
+        server.start();
+
+        let mut fj = Forgejo::with_url(
+            ureq::agent(),
+            Some("bad".to_string()),
+            &url,
+            "owner",
+            "index",
+        );
+        assert!(matches!(
+            fj.get_latest("ripgrep"),
+            Err(InteractError::Unauthorized)
+        ));
+    }
+}
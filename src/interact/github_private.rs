@@ -1,4 +1,7 @@
-use crate::interact::{Interact, InteractError};
+use crate::{
+    error::PrebuiltError,
+    interact::{http_cache, HttpCache, Interact, InteractError},
+};
 use serde::{de::DeserializeOwned, Deserialize};
 use std::collections::HashMap;
 use ureq::{Agent, Error};
@@ -22,15 +25,23 @@ pub struct GithubPrivate {
     u_repo: String,
     stable_index: Option<Release>,
     index: HashMap<String, Release>,
+    cache: Option<HttpCache>, // Only used for the stable index
 }
 impl GithubPrivate {
-    pub fn new(agent: Agent, auth_token: String, slug: &str) -> Self {
+    pub fn new(
+        agent: Agent,
+        auth_token: String,
+        slug: &str,
+        cache: Option<HttpCache>,
+    ) -> Result<Self, PrebuiltError> {
         let s: Vec<&str> = slug.split('/').collect();
         if s.len() != 3 {
-            panic!("Slug '{slug}' is not formatted properly.");
+            return Err(PrebuiltError::Config(format!(
+                "Slug '{slug}' is not formatted properly."
+            )));
         }
 
-        Self {
+        Ok(Self {
             agent,
             auth_token,
             u_url: format!("https://api.{}", s[0]),
@@ -38,11 +49,12 @@ impl GithubPrivate {
             u_repo: s[2].to_string(),
             stable_index: None,
             index: HashMap::new(),
-        }
+            cache,
+        })
     }
 
     fn api_call<T: DeserializeOwned>(&self, url: &str) -> Result<T, InteractError> {
-        return match self
+        match self
             .agent
             .get(url)
             .set("Accept", "application/vnd.github+json")
@@ -55,17 +67,28 @@ impl GithubPrivate {
         {
             Ok(res) => {
                 let s = res.into_string().map_err(|_| InteractError::Malformed)?;
-                let json = serde_json::from_str(&s)
-                    .unwrap_or_else(|_| panic!("Could not parse api json from {url}"));
-                Ok(json)
+                serde_json::from_str(&s).map_err(|_| InteractError::Malformed)
             }
             Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
             Err(_) => Err(InteractError::ConnectionError),
-        };
+        }
+    }
+
+    fn cached_call(&self, url: &str) -> Result<String, InteractError> {
+        let req = self
+            .agent
+            .get(url)
+            .set("Accept", "application/octet-stream")
+            .set(
+                "Authorization",
+                format!("Bearer {}", self.auth_token).as_str(),
+            );
+        let s = http_cache::call(self.cache.as_ref(), req)?;
+        Ok(s.trim().to_string())
     }
 
     fn call(&self, url: &str) -> Result<String, InteractError> {
-        return match self
+        match self
             .agent
             .get(url)
             .set("Accept", "application/octet-stream")
@@ -81,7 +104,7 @@ impl GithubPrivate {
             }
             Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
             Err(_) => Err(InteractError::ConnectionError),
-        };
+        }
     }
 
     fn get_str_file(
@@ -118,23 +141,52 @@ impl GithubPrivate {
             }
         }
     }
-}
-impl Interact for GithubPrivate {
-    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+
+    fn get_stable_index(&mut self) -> Result<&Release, InteractError> {
         if self.stable_index.is_none() {
-            self.stable_index = Some(self.api_call(&format!(
+            let url = format!(
                 "{}/repos/{}/{}/releases/tags/stable-index",
                 self.u_url, self.u_owner, self.u_repo
-            ))?)
+            );
+            let req = self
+                .agent
+                .get(&url)
+                .set("Accept", "application/vnd.github+json")
+                .set("X-GitHub-Api-Version", "2022-11-28")
+                .set(
+                    "Authorization",
+                    format!("Bearer {}", self.auth_token).as_str(),
+                );
+            let s = http_cache::call(self.cache.as_ref(), req)?;
+            self.stable_index =
+                Some(serde_json::from_str(&s).map_err(|_| InteractError::Malformed)?);
         }
 
+        Ok(self
+            .stable_index
+            .as_ref()
+            .expect("Should have stable index!"))
+    }
+}
+impl Interact for GithubPrivate {
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        let si = self.get_stable_index()?;
+        Ok(si.assets.iter().map(|a| a.name.clone()).collect())
+    }
+
+    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+        self.get_stable_index()?;
+
         // Get latest from file
         let si = self
             .stable_index
             .as_ref()
             .expect("Should have stable index!");
 
-        self.get_str_file(&si.assets, id)
+        match si.assets.iter().find(|a| a.name.eq(id)) {
+            Some(asset) => self.cached_call(&asset.url),
+            None => Err(InteractError::HttpCode(404)),
+        }
     }
 
     fn get_str(
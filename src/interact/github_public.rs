@@ -1,14 +1,38 @@
-use crate::interact::{Interact, InteractError};
+use crate::interact::{http_cache, HttpCache, Interact, InteractError};
+use serde::Deserialize;
 use ureq::{Agent, Error};
 
+#[derive(Clone, Debug, Deserialize)]
+struct Release {
+    assets: Vec<ReleaseAssets>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ReleaseAssets {
+    name: String,
+}
+
 pub struct GithubPublic {
     agent: Agent,
     pre_url: String,
+    api_url: Option<String>,
+    cache: Option<HttpCache>, // Only used for the stable index
 }
 impl GithubPublic {
-    pub fn new(agent: Agent, slug: &str) -> Self {
+    pub fn new(agent: Agent, slug: &str, cache: Option<HttpCache>) -> Self {
         let pre_url = format!("https://{}/releases/download", slug);
-        Self { agent, pre_url }
+
+        // Listing releases is only possible through the api.
+        let api_url = slug
+            .split_once('/')
+            .map(|(host, repo)| format!("https://api.{host}/repos/{repo}/releases/tags"));
+
+        Self {
+            agent,
+            pre_url,
+            api_url,
+            cache,
+        }
     }
 
     fn url(&self, id: &str, version: &str, file: &str) -> String {
@@ -16,20 +40,37 @@ impl GithubPublic {
     }
 
     fn call(&self, url: &str) -> Result<String, InteractError> {
-        return match self.agent.get(url).call() {
+        match self.agent.get(url).call() {
             Ok(res) => {
                 let s = res.into_string().map_err(|_| InteractError::Malformed)?;
                 Ok(s.trim().to_string())
             }
             Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
             Err(_) => Err(InteractError::ConnectionError),
-        };
+        }
     }
 }
 impl Interact for GithubPublic {
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        let url = match &self.api_url {
+            Some(url) => format!("{url}/stable-index"),
+            None => return Err(InteractError::Unsupported),
+        };
+
+        let req = self
+            .agent
+            .get(&url)
+            .set("Accept", "application/vnd.github+json")
+            .set("X-GitHub-Api-Version", "2022-11-28");
+        let s = http_cache::call(self.cache.as_ref(), req)?;
+        let release: Release = serde_json::from_str(&s).map_err(|_| InteractError::Malformed)?;
+        Ok(release.assets.into_iter().map(|a| a.name).collect())
+    }
+
     fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
         let url = format!("{}/stable-index/{id}", self.pre_url);
-        self.call(&url)
+        let s = http_cache::call(self.cache.as_ref(), self.agent.get(&url))?;
+        Ok(s.trim().to_string())
     }
 
     fn get_str(
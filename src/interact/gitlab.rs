@@ -0,0 +1,395 @@
+use crate::{
+    error::PrebuiltError,
+    interact::{Interact, InteractError},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::HashMap;
+use ureq::{Agent, Error, Request};
+
+#[derive(Clone, Debug, Deserialize)]
+struct Release {
+    assets: ReleaseAssets,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ReleaseAssets {
+    links: Vec<ReleaseLink>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ReleaseLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+impl ReleaseLink {
+    fn download_url(&self) -> &str {
+        self.direct_asset_url.as_deref().unwrap_or(&self.url)
+    }
+}
+
+enum Token {
+    Private(String),
+    Job(String),
+}
+
+/// Gitlab index, public if there is no auth token.
+pub struct Gitlab {
+    agent: Agent,
+    auth_token: Option<Token>,
+    origin: String, // Only urls on this origin are sent the auth token
+    u_url: String,
+    stable_index: Option<Release>,
+    index: HashMap<String, Release>,
+}
+impl Gitlab {
+    pub fn new(
+        agent: Agent,
+        auth_token: Option<String>,
+        slug: &str,
+    ) -> Result<Self, PrebuiltError> {
+        // Gitlab allows nested groups, so everything after the host is the project path.
+        match slug.split_once('/') {
+            Some((host, project)) if project.contains('/') => Ok(Self::with_url(
+                agent,
+                auth_token,
+                &format!("https://{host}"),
+                project,
+            )),
+            _ => Err(PrebuiltError::Config(format!(
+                "Slug '{slug}' is not formatted properly."
+            ))),
+        }
+    }
+
+    fn with_url(agent: Agent, auth_token: Option<String>, url: &str, project: &str) -> Self {
+        // CI job tokens use a different header than personal/project access tokens.
+        let auth_token = auth_token.map(|token| match token.strip_prefix("job:") {
+            Some(token) => Token::Job(token.to_string()),
+            None => Token::Private(token),
+        });
+
+        Self {
+            agent,
+            auth_token,
+            origin: url.trim_end_matches('/').to_string(),
+            u_url: format!(
+                "{url}/api/v4/projects/{}",
+                project.trim_matches('/').replace('/', "%2F")
+            ),
+            stable_index: None,
+            index: HashMap::new(),
+        }
+    }
+
+    fn request(&self, url: &str) -> Request {
+        let req = self.agent.get(url);
+
+        // Release links can point anywhere, so never hand the token to another host.
+        let same_origin = url
+            .strip_prefix(&self.origin)
+            .is_some_and(|path| path.starts_with('/'));
+        match &self.auth_token {
+            Some(Token::Private(token)) if same_origin => req.set("PRIVATE-TOKEN", token),
+            Some(Token::Job(token)) if same_origin => req.set("JOB-TOKEN", token),
+            _ => req,
+        }
+    }
+
+    fn api_call<T: DeserializeOwned>(&self, url: &str) -> Result<T, InteractError> {
+        match self.request(url).set("Accept", "application/json").call() {
+            Ok(res) => {
+                let s = res.into_string().map_err(|_| InteractError::Malformed)?;
+                serde_json::from_str(&s).map_err(|_| InteractError::Malformed)
+            }
+            Err(Error::Status(401 | 403, _)) if self.auth_token.is_some() => {
+                Err(InteractError::Unauthorized)
+            }
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn call(&self, url: &str) -> Result<Vec<u8>, InteractError> {
+        match self.request(url).call() {
+            Ok(response) => {
+                let mut bytes = Vec::new();
+                //TODO: Allow limiting of size.
+                response
+                    .into_reader()
+                    .read_to_end(&mut bytes)
+                    .map_err(|_| InteractError::Malformed)?;
+                Ok(bytes)
+            }
+            Err(Error::Status(401 | 403, _)) if self.auth_token.is_some() => {
+                Err(InteractError::Unauthorized)
+            }
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        }
+    }
+
+    fn get_file(&self, release: &Release, file: &str) -> Result<Vec<u8>, InteractError> {
+        match release.assets.links.iter().find(|l| l.name.eq(file)) {
+            Some(link) => self.call(link.download_url()),
+            None => Err(InteractError::HttpCode(404)),
+        }
+    }
+
+    fn get_str_file(&self, release: &Release, file: &str) -> Result<String, InteractError> {
+        let bytes = self.get_file(release, file)?;
+        let s = String::from_utf8(bytes).map_err(|_| InteractError::Malformed)?;
+        Ok(s.trim().to_string())
+    }
+
+    fn get_release(&mut self, id: &str, version: &str) -> Result<Release, InteractError> {
+        let key = format!("{id}/--/{version}");
+        match self.index.get(&key) {
+            Some(item) => Ok(item.clone()),
+            None => {
+                let rel = self.api_call(&format!("{}/releases/{id}-{version}", self.u_url))?;
+                let _ = self.index.insert(key.clone(), rel);
+                Ok(self.index.get(&key).unwrap().clone())
+            }
+        }
+    }
+
+    fn get_stable_index(&mut self) -> Result<&Release, InteractError> {
+        if self.stable_index.is_none() {
+            self.stable_index =
+                Some(self.api_call(&format!("{}/releases/stable-index", self.u_url))?)
+        }
+
+        Ok(self
+            .stable_index
+            .as_ref()
+            .expect("Should have stable index!"))
+    }
+}
+impl Interact for Gitlab {
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        let si = self.get_stable_index()?;
+        Ok(si.assets.links.iter().map(|a| a.name.clone()).collect())
+    }
+
+    fn get_latest(&mut self, id: &str) -> Result<String, InteractError> {
+        self.get_stable_index()?;
+
+        // Get latest from file
+        let si = self
+            .stable_index
+            .as_ref()
+            .expect("Should have stable index!");
+
+        self.get_str_file(si, id)
+    }
+
+    fn get_str(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<String, InteractError> {
+        let release = self.get_release(id, version)?;
+        self.get_str_file(&release, file_name)
+    }
+
+    fn get_blob(
+        &mut self,
+        id: &str,
+        version: &str,
+        file_name: &str,
+    ) -> Result<Vec<u8>, InteractError> {
+        let release = self.get_release(id, version)?;
+        self.get_file(&release, file_name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Gitlab;
+    use crate::interact::{test_server::TestServer, Interact, InteractError};
+
+    static API: &str = "/api/v4/projects/group%2Fsub%2Findex/releases";
+
+    fn release(url: &str, files: &[&str]) -> String {
+        let links: Vec<String> = files
+            .iter()
+            .map(|f| {
+                format!(
+                    r#"{{"id":1,"name":"{f}","url":"{url}/uploads/{f}","direct_asset_url":"{url}/dl/{f}","link_type":"other"}}"#
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"tag_name":"t","assets":{{"count":{},"sources":[],"links":[{}]}}}}"#,
+            files.len(),
+            links.join(",")
+        )
+    }
+
+    fn server(code: u16) -> (TestServer, String) {
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route(
+            &format!("{API}/stable-index"),
+            code,
+            release(&url, &["ripgrep"]),
+        );
+        server.route("/dl/ripgrep", code, "14.1.0\n");
+        server.route(
+            &format!("{API}/ripgrep-14.1.0"),
+            code,
+            release(&url, &["info.json", "x86_64-unknown-linux-gnu.tar.gz"]),
+        );
+        server.route("/dl/info.json", code, "{}");
+        server.route("/dl/x86_64-unknown-linux-gnu.tar.gz", code, vec![0, 1, 2]);
+        (server, url)
+    }
+
+    fn check(gl: &mut Gitlab) {
+        assert_eq!(gl.get_ids().unwrap(), vec!["ripgrep".to_string()]);
+        assert_eq!(gl.get_latest("ripgrep").unwrap(), "14.1.0");
+        assert_eq!(gl.get_str("ripgrep", "14.1.0", "info.json").unwrap(), "{}");
+        assert_eq!(
+            gl.get_blob("ripgrep", "14.1.0", "x86_64-unknown-linux-gnu.tar.gz")
+                .unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(matches!(
+            gl.get_str("ripgrep", "14.1.0", "hashes.json"),
+            Err(InteractError::HttpCode(404))
+        ));
+        assert!(matches!(
+            gl.get_str("ripgrep", "0.0.0", "info.json"),
+            Err(InteractError::HttpCode(404))
+        ));
+    }
+
+    #[test]
+    fn test_gitlab_public() {
+        let (server, url) = server(200);
+        let requests = server.start();
+
+        let mut gl = Gitlab::with_url(ureq::agent(), None, &url, "group/sub/index");
+        check(&mut gl);
+
+        // Releases are only looked up once.
+        let requests = requests.lock().unwrap();
+        let lookups = requests
+            .iter()
+            .filter(|r| r.path.eq(&format!("{API}/ripgrep-14.1.0")))
+            .count();
+        assert_eq!(lookups, 1);
+        assert!(requests
+            .iter()
+            .all(|r| !r.headers.contains_key("private-token")));
+    }
+
+    #[test]
+    fn test_gitlab_private() {
+        let (server, url) = server(200);
+        let requests = server.start();
+
+        let mut gl = Gitlab::with_url(
+            ureq::agent(),
+            Some("glpat-token".to_string()),
+            &url,
+            "group/sub/index",
+        );
+        check(&mut gl);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| r.headers.get("private-token").map(|t| t.as_str()) == Some("glpat-token")));
+    }
+
+    #[test]
+    fn test_gitlab_private_job_token() {
+        let (server, url) = server(200);
+        let requests = server.start();
+
+        let mut gl = Gitlab::with_url(
+            ureq::agent(),
+            Some("job:glcbt-token".to_string()),
+            &url,
+            "group/sub/index",
+        );
+        assert_eq!(gl.get_latest("ripgrep").unwrap(), "14.1.0");
+
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|r| {
+            r.headers.get("job-token").map(|t| t.as_str()) == Some("glcbt-token")
+                && !r.headers.contains_key("private-token")
+        }));
+    }
+
+    #[test]
+    fn test_gitlab_private_other_host() {
+        // Links to a second server, which must never see the token.
+        let mut other = TestServer::new();
+        let other_url = other.url();
+        other.route("/dl/info.json", 200, "{}");
+        let other_requests = other.start();
+
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route(
+            &format!("{API}/ripgrep-14.1.0"),
+            200,
+            release(&other_url, &["info.json"]),
+        );
+        let requests = server.start();
+
+        let mut gl = Gitlab::with_url(
+            ureq::agent(),
+            Some("glpat-token".to_string()),
+            &url,
+            "group/sub/index",
+        );
+        assert_eq!(gl.get_str("ripgrep", "14.1.0", "info.json").unwrap(), "{}");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].headers.contains_key("private-token"));
+        let other_requests = other_requests.lock().unwrap();
+        assert_eq!(other_requests.len(), 1);
+        assert!(!other_requests[0].headers.contains_key("private-token"));
+    }
+
+    #[test]
+    fn test_gitlab_malformed() {
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route(&format!("{API}/stable-index"), 200, "<html></html>");
+        server.start();
+
+        let mut gl = Gitlab::with_url(ureq::agent(), None, &url, "group/sub/index");
+        assert!(matches!(
+            gl.get_latest("ripgrep"),
+            Err(InteractError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_gitlab_private_bad_token() {
+        let (server, url) = server(401);
+        server.start();
+
+        let mut gl = Gitlab::with_url(
+            ureq::agent(),
+            Some("bad".to_string()),
+            &url,
+            "group/sub/index",
+        );
+        assert!(matches!(
+            gl.get_latest("ripgrep"),
+            Err(InteractError::Unauthorized)
+        ));
+        assert!(matches!(
+            gl.get_str("ripgrep", "14.1.0", "info.json"),
+            Err(InteractError::Unauthorized)
+        ));
+    }
+}
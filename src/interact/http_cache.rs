@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use ureq::{Error, Request};
+
+use crate::{data::HASH_ORDER, interact::InteractError};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched: u64, // Unix time in seconds
+    body: String,
+}
+
+/// Index metadata responses stored on disk, revalidated with ETag/Last-Modified once stale.
+#[derive(Clone, Debug)]
+pub struct HttpCache {
+    dir: PathBuf,
+    ttl: u64, // Seconds a response is used without asking the server
+}
+impl HttpCache {
+    pub fn new(dir: PathBuf, ttl: u64) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// File name has to stay the same across builds, so use a stable hash of the url.
+    fn path(&self, url: &str) -> PathBuf {
+        let name = match HASH_ORDER.first() {
+            Some(hash_type) => hash_type.digest(url.as_bytes()),
+            // Builds without hashes fall back to the url itself.
+            None => url
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c
+                    }
+                    else {
+                        '_'
+                    }
+                })
+                .take(200)
+                .collect(),
+        };
+        self.dir.join(format!("{name}.json"))
+    }
+
+    fn load(&self, url: &str) -> Option<Entry> {
+        let raw = fs::read_to_string(self.path(url)).ok()?;
+        let entry: Entry = serde_json::from_str(&raw).ok()?;
+        // Guard against hash collisions.
+        entry.url.eq(url).then_some(entry)
+    }
+
+    fn save(&self, entry: &Entry) {
+        // Failing to cache is not an error, the next run will just ask again.
+        if create_dir_all(&self.dir).is_ok() {
+            if let Ok(raw) = serde_json::to_string(entry) {
+                let _ = fs::write(self.path(&entry.url), raw);
+            }
+        }
+    }
+}
+
+/// Send a GET request, going through the cache if there is one.
+pub fn call(cache: Option<&HttpCache>, mut req: Request) -> Result<String, InteractError> {
+    let Some(cache) = cache
+    else {
+        return match req.call() {
+            Ok(res) => res.into_string().map_err(|_| InteractError::Malformed),
+            Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+            Err(_) => Err(InteractError::ConnectionError),
+        };
+    };
+
+    let url = req.url().to_string();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let entry = cache.load(&url);
+    if let Some(entry) = &entry {
+        if now.saturating_sub(entry.fetched) < cache.ttl {
+            return Ok(entry.body.clone());
+        }
+
+        if let Some(etag) = &entry.etag {
+            req = req.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            req = req.set("If-Modified-Since", last_modified);
+        }
+    }
+
+    match req.call() {
+        Ok(res) if res.status() == 304 => match entry {
+            Some(mut entry) => {
+                entry.fetched = now;
+                cache.save(&entry);
+                Ok(entry.body)
+            }
+            None => Err(InteractError::HttpCode(304)),
+        },
+        Ok(res) => {
+            let etag = res.header("ETag").map(|s| s.to_string());
+            let last_modified = res.header("Last-Modified").map(|s| s.to_string());
+            let body = res.into_string().map_err(|_| InteractError::Malformed)?;
+
+            // Responses that cannot be revalidated are only kept for the ttl.
+            cache.save(&Entry {
+                url,
+                etag,
+                last_modified,
+                fetched: now,
+                body: body.clone(),
+            });
+            Ok(body)
+        }
+        Err(Error::Status(code, _)) => Err(InteractError::HttpCode(code)),
+        Err(_) => Err(InteractError::ConnectionError),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{call, HttpCache};
+    use crate::interact::test_server::TestServer;
+
+    #[test]
+    fn test_http_cache() {
+        let mut server = TestServer::new();
+        let url = server.url();
+        server.route_etag("/stable-index/ripgrep", "\"v1\"", "14.1.0");
+        let requests = server.start();
+
+        let dir = std::env::temp_dir().join(format!("prebuilt-http-cache-{}", std::process::id()));
+        let agent = ureq::agent();
+        let get = |cache: &HttpCache| {
+            call(
+                Some(cache),
+                agent.get(&format!("{url}/stable-index/ripgrep")),
+            )
+            .unwrap()
+        };
+
+        // Fresh responses are not requested again
+        let cache = HttpCache::new(dir.clone(), 60);
+        assert_eq!(get(&cache), "14.1.0");
+        assert_eq!(get(&cache), "14.1.0");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // Stale responses are revalidated
+        let cache = HttpCache::new(dir.clone(), 0);
+        assert_eq!(get(&cache), "14.1.0");
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[1].headers["if-none-match"], "\"v1\"");
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
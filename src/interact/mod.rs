@@ -1,16 +1,36 @@
 use ureq::Agent;
 
-use crate::color::{err_color_print, PossibleColor};
+use crate::{
+    color::{err_color_print, PossibleColor},
+    data::ConfigFileHttp,
+    error::PrebuiltError,
+};
 
+#[cfg(any(feature = "custom-http-public", feature = "custom-http-private"))]
+mod custom_http;
+#[cfg(feature = "file")]
+mod file;
+#[cfg(any(feature = "forgejo-public", feature = "forgejo-private"))]
+mod forgejo;
 #[cfg(feature = "github-private")]
 mod github_private;
 #[cfg(feature = "github-public")]
 mod github_public;
+#[cfg(any(feature = "gitlab-public", feature = "gitlab-private"))]
+mod gitlab;
+mod http_cache;
+
+pub use http_cache::HttpCache;
+
+#[cfg(test)]
+mod test_server;
 
 #[derive(Debug)]
 pub enum InteractError {
     Malformed,
     HttpCode(u16),
+    Unauthorized,
+    Unsupported,
     ConnectionError,
     // #[error("Unknown error")]
     // Unknown,
@@ -20,13 +40,32 @@ impl std::fmt::Display for InteractError {
         match self {
             InteractError::Malformed => write!(f, "The received string is malformed."),
             InteractError::HttpCode(code) => write!(f, "Http code {code}"),
+            InteractError::Unauthorized => write!(f, "The auth token was rejected"),
+            InteractError::Unsupported => write!(f, "Not supported by this index"),
             InteractError::ConnectionError => write!(f, "Connection error"),
         }
     }
 }
 impl std::error::Error for InteractError {}
 
-pub fn create_interact(input: String, auth: Option<&String>, agent: Agent) -> Box<dyn Interact> {
+pub fn create_interact(
+    input: String,
+    auth: Option<&String>,
+    http: &ConfigFileHttp,
+    cache: Option<&HttpCache>,
+    agent: Agent,
+    quiet: bool,
+) -> Result<Box<dyn Interact>, PrebuiltError> {
+    // Fetchers made for --jobs threads do not announce the index again.
+    let using = |index: String| {
+        if !quiet {
+            eprintln!(
+                "{} index {index}.",
+                err_color_print("Using", PossibleColor::BrightCyan),
+            );
+        }
+    };
+
     // Github public
     if input.starts_with("gh-pub:") {
         #[cfg(feature = "github-public")]
@@ -34,14 +73,17 @@ pub fn create_interact(input: String, auth: Option<&String>, agent: Agent) -> Bo
             let url = input
                 .get(7..input.len())
                 .expect("Missing url after gh-pub:");
-            eprintln!(
-                "{} index https://{url}.",
-                err_color_print("Using", PossibleColor::BrightCyan),
-            );
-            return Box::new(github_public::GithubPublic::new(agent, url));
+            using(format!("https://{url}"));
+            return Ok(Box::new(github_public::GithubPublic::new(
+                agent,
+                url,
+                cache.cloned(),
+            )));
         }
         #[cfg(not(feature = "github-public"))]
-        panic!("Using this index ({input}) requires the github-public feature!");
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the github-public feature!"
+        )));
     }
 
     // Github private
@@ -51,24 +93,163 @@ pub fn create_interact(input: String, auth: Option<&String>, agent: Agent) -> Bo
             let url = input
                 .get(7..input.len())
                 .expect("Missing url after gh-pri:");
-            eprintln!(
-                "{} index https://{url}.",
-                err_color_print("Using", PossibleColor::BrightCyan),
-            );
-            return Box::new(github_private::GithubPrivate::new(
+            using(format!("https://{url}"));
+            return Ok(Box::new(github_private::GithubPrivate::new(
                 agent,
-                auth.expect("Need auth token for private index.").clone(),
+                auth.ok_or_else(|| {
+                    PrebuiltError::Auth("Need auth token for private index.".to_string())
+                })?
+                .clone(),
                 url,
-            ));
+                cache.cloned(),
+            )?));
         }
         #[cfg(not(feature = "github-private"))]
-        panic!("Using this index ({input}) requires the github-private feature!");
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the github-private feature!"
+        )));
+    }
+
+    // Gitlab public
+    if input.starts_with("gl-pub:") {
+        #[cfg(feature = "gitlab-public")]
+        {
+            let url = input
+                .get(7..input.len())
+                .expect("Missing url after gl-pub:");
+            using(format!("https://{url}"));
+            return Ok(Box::new(gitlab::Gitlab::new(agent, None, url)?));
+        }
+        #[cfg(not(feature = "gitlab-public"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the gitlab-public feature!"
+        )));
+    }
+
+    // Gitlab private
+    if input.starts_with("gl-pri:") {
+        #[cfg(feature = "gitlab-private")]
+        {
+            let url = input
+                .get(7..input.len())
+                .expect("Missing url after gl-pri:");
+            using(format!("https://{url}"));
+            return Ok(Box::new(gitlab::Gitlab::new(
+                agent,
+                Some(
+                    auth.ok_or_else(|| {
+                        PrebuiltError::Auth("Need auth token for private index.".to_string())
+                    })?
+                    .clone(),
+                ),
+                url,
+            )?));
+        }
+        #[cfg(not(feature = "gitlab-private"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the gitlab-private feature!"
+        )));
+    }
+
+    // Forgejo/Gitea public
+    if input.starts_with("fj-pub:") || input.starts_with("gt-pub:") {
+        #[cfg(feature = "forgejo-public")]
+        {
+            let url = input
+                .get(7..input.len())
+                .expect("Missing url after fj-pub:");
+            using(format!("https://{url}"));
+            return Ok(Box::new(forgejo::Forgejo::new(agent, None, url)?));
+        }
+        #[cfg(not(feature = "forgejo-public"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the forgejo-public or gitea-public feature!"
+        )));
+    }
+
+    // Forgejo/Gitea private
+    if input.starts_with("fj-pri:") || input.starts_with("gt-pri:") {
+        #[cfg(feature = "forgejo-private")]
+        {
+            let url = input
+                .get(7..input.len())
+                .expect("Missing url after fj-pri:");
+            using(format!("https://{url}"));
+            return Ok(Box::new(forgejo::Forgejo::new(
+                agent,
+                Some(
+                    auth.ok_or_else(|| {
+                        PrebuiltError::Auth("Need auth token for private index.".to_string())
+                    })?
+                    .clone(),
+                ),
+                url,
+            )?));
+        }
+        #[cfg(not(feature = "forgejo-private"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the forgejo-private or gitea-private feature!"
+        )));
+    }
+
+    // Custom http
+    if input.starts_with("http:") {
+        let url = input.get(5..input.len()).expect("Missing url after http:");
+
+        #[cfg(feature = "custom-http-private")]
+        if let Some(auth) = auth {
+            using(format!("https://{url}"));
+            return Ok(Box::new(custom_http::CustomHttp::new(
+                agent,
+                Some(auth.clone()),
+                http,
+                url,
+            )));
+        }
+        #[cfg(not(feature = "custom-http-private"))]
+        if auth.is_some() {
+            return Err(PrebuiltError::Unsupported(format!(
+                "Using this index ({input}) with auth requires the custom-http-private feature!"
+            )));
+        }
+
+        #[cfg(feature = "custom-http-public")]
+        {
+            using(format!("https://{url}"));
+            return Ok(Box::new(custom_http::CustomHttp::new(
+                agent, None, http, url,
+            )));
+        }
+        #[cfg(not(feature = "custom-http-public"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the custom-http-public feature!"
+        )));
+    }
+
+    // Local directory
+    if input.starts_with("file:") {
+        #[cfg(feature = "file")]
+        {
+            let path = input.get(5..input.len()).expect("Missing path after file:");
+            using(path.to_string());
+            return Ok(Box::new(file::FileIndex::new(path)?));
+        }
+        #[cfg(not(feature = "file"))]
+        return Err(PrebuiltError::Unsupported(format!(
+            "Using this index ({input}) requires the file feature!"
+        )));
     }
 
-    panic!("This index ({input}) is not supported or malformed.");
+    Err(PrebuiltError::Config(format!(
+        "This index ({input}) is not supported or malformed."
+    )))
 }
 
 pub trait Interact {
+    /// Ids of every crate in the stable index.
+    fn get_ids(&mut self) -> Result<Vec<String>, InteractError> {
+        Err(InteractError::Unsupported)
+    }
     fn get_latest(&mut self, id: &str) -> Result<String, InteractError>;
     fn get_str(
         &mut self,
@@ -83,3 +264,31 @@ pub trait Interact {
         file_name: &str,
     ) -> Result<Vec<u8>, InteractError>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::create_interact;
+    use crate::{data::ConfigFileHttp, error::PrebuiltError};
+
+    #[test]
+    fn test_malformed_index() {
+        let auth = "token".to_string();
+        for index in [
+            "gl-pub:gitlab.com/foo",
+            "gl-pri:gitlab.com/foo",
+            "fj-pri:host/x",
+            "gh-pri:github.com/x",
+            "file:/missing",
+        ] {
+            let res = create_interact(
+                index.to_string(),
+                Some(&auth),
+                &ConfigFileHttp::default(),
+                None,
+                ureq::agent(),
+                true,
+            );
+            assert!(matches!(res, Err(PrebuiltError::Config(_))), "{index}");
+        }
+    }
+}
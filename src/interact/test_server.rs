@@ -0,0 +1,108 @@
+//! A tiny blocking HTTP/1.1 stand-in so index backends can be tested without network access.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+pub struct TestServer {
+    listener: TcpListener,
+    routes: HashMap<String, (u16, Vec<u8>)>,
+    etags: HashMap<String, String>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+impl TestServer {
+    pub fn new() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind test server.");
+        Self {
+            listener,
+            routes: HashMap::new(),
+            etags: HashMap::new(),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!(
+            "http://{}",
+            self.listener
+                .local_addr()
+                .expect("Test server has no address.")
+        )
+    }
+
+    pub fn route(&mut self, path: &str, code: u16, body: impl Into<Vec<u8>>) {
+        self.routes.insert(path.to_string(), (code, body.into()));
+    }
+
+    /// Like route, but answers with 304 when the request has a matching If-None-Match.
+    pub fn route_etag(&mut self, path: &str, etag: &str, body: impl Into<Vec<u8>>) {
+        self.route(path, 200, body);
+        self.etags.insert(path.to_string(), etag.to_string());
+    }
+
+    /// Serve the registered routes on a background thread, returning a log of received requests.
+    pub fn start(self) -> Arc<Mutex<Vec<Request>>> {
+        let requests = self.requests.clone();
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let Ok(mut stream) = stream
+                else {
+                    continue;
+                };
+
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                if reader.read_line(&mut line).is_err() {
+                    continue;
+                }
+                let path = line.split(' ').nth(1).unwrap_or("/").to_string();
+
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((k, v)) = line.split_once(':') {
+                        headers.insert(k.trim().to_lowercase(), v.trim().to_string());
+                    }
+                }
+
+                let (mut code, mut body) = self
+                    .routes
+                    .get(&path)
+                    .cloned()
+                    .unwrap_or((404, b"Not Found".to_vec()));
+                let etag = self.etags.get(&path);
+                if etag.is_some() && etag == headers.get("if-none-match") {
+                    code = 304;
+                    body = Vec::new();
+                }
+                let etag = etag.map(|e| format!("ETag: {e}\r\n")).unwrap_or_default();
+                self.requests
+                    .lock()
+                    .unwrap()
+                    .push(Request { path, headers });
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {code} Test\r\n{etag}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+
+        requests
+    }
+}
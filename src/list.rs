@@ -0,0 +1,47 @@
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    error::PrebuiltError,
+    events,
+    state::{self, BinStatus, State},
+};
+
+/// Print every crate in the state file, flagging binaries that changed since they were installed.
+pub fn list(config: &Config) -> Result<(), PrebuiltError> {
+    let state = State::load(config)?;
+
+    if state.crates().is_empty() {
+        eprintln!("No crates are installed by cargo-prebuilt.");
+    }
+
+    for (id, installed) in state.crates() {
+        let version = &installed.version;
+        eprintln!(
+            "{} {id}@{version} for target {} from index {}.",
+            err_color_print("Installed", PossibleColor::BrightGreen),
+            installed.target,
+            installed.index
+        );
+        events::listed(id, config, installed);
+
+        for bin in installed.bins.iter() {
+            let status = state::bin_status(bin);
+            match status {
+                BinStatus::Ok => eprintln!("    {:?}", bin.path),
+                BinStatus::Modified => eprintln!(
+                    "    {:?} {}",
+                    bin.path,
+                    err_color_print("(Modified)", PossibleColor::BrightYellow)
+                ),
+                BinStatus::Missing => eprintln!(
+                    "    {:?} {}",
+                    bin.path,
+                    err_color_print("(Missing)", PossibleColor::BrightRed)
+                ),
+            }
+            events::bin_listed(id, version, config, &bin.path, status);
+        }
+    }
+
+    Ok(())
+}
@@ -1,22 +1,47 @@
+mod cache;
 mod color;
 mod config;
 mod data;
+mod error;
 mod events;
 mod get;
 mod interact;
+mod list;
+mod mirror;
+mod pin;
+mod rollback;
+mod stage;
+mod state;
+mod store;
+mod uninstall;
+mod upgrade;
+mod verify;
 
 use flate2::read::GzDecoder;
+use indexmap::IndexSet;
 use std::{
-    fs::{self, create_dir_all, File},
-    io::{Read, Write},
-    path::Path,
+    fs::{self, create_dir_all},
+    io::Read,
+    path::{Path, PathBuf},
+    process::ExitCode,
     str,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use tar::Archive;
 
 use crate::{
     color::{err_color_print, PossibleColor},
+    config::{Config, Mode},
+    data::{Hashes, HashesFileImm, InfoFileImm, InstalledBinV1, InstalledCrateV1},
+    error::PrebuiltError,
     get::Fetcher,
+    stage::Stage,
+    state::State,
 };
 
 #[cfg(feature = "mimalloc")]
@@ -30,175 +55,577 @@ static APPLICATION: &str = "cargo-prebuilt";
 static DEFAULT_INDEX: &str = "gh-pub:github.com/cargo-prebuilt/index";
 static TARGET: &str = env!("TARGET");
 
-fn main() -> Result<(), String> {
-    let config = config::get();
+fn main() -> ExitCode {
+    match run() {
+        Ok(_) => {
+            eprintln!("{}", err_color_print("Done!", PossibleColor::Green));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!(
+                "{} {err}",
+                err_color_print("Error:", PossibleColor::BrightRed)
+            );
+            (&err).into()
+        }
+    }
+}
+
+fn run() -> Result<(), PrebuiltError> {
+    let config = config::get()?;
     let config = &config;
     #[cfg(debug_assertions)]
     dbg!(&config);
 
+    run_mode(config)
+}
+
+/// Run the mode in config, every failure gets an error event.
+fn run_mode(config: &Config) -> Result<(), PrebuiltError> {
+    mode(config).map_err(|err| events::unreported(config, err))
+}
+
+fn mode(config: &Config) -> Result<(), PrebuiltError> {
     // Check if a needed feature was excluded.
-    should_error();
+    should_error()?;
+
+    // Build ureq agent
+    let agent = create_agent()?;
 
+    // Create Fetcher which is used to fetch items from index.
+    match &config.mode {
+        Mode::Install => install(config, &mut Fetcher::new(config, agent)?, &config.pkgs),
+        Mode::Mirror(dir) => mirror::mirror(config, &mut Fetcher::new(config, agent)?, dir),
+        Mode::Verify => verify::verify(config, &mut Fetcher::new(config, agent)?),
+        Mode::List => list::list(config),
+        Mode::Uninstall { force } => uninstall::uninstall(config, *force),
+        Mode::Outdated => upgrade::outdated(config, &mut Fetcher::new(config, agent)?),
+        Mode::Upgrade => upgrade::upgrade(config, &mut Fetcher::new(config, agent)?),
+        Mode::Pin => pin::pin(config),
+        Mode::Unpin => pin::unpin(config),
+        Mode::Rollback => rollback::rollback(config),
+        Mode::CacheClean => {
+            cache::clean(config);
+            Ok(())
+        }
+    }
+}
+
+fn install(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    pkgs: &IndexSet<String>,
+) -> Result<(), PrebuiltError> {
     if !config.no_create_path && create_dir_all(&config.path).is_err() {
-        panic!("Could not create the directory '{:?}'.", config.path);
+        return Err(PrebuiltError::Io(format!(
+            "Could not create the directory '{:?}'.",
+            config.path
+        )));
     }
     else if !Path::new(&config.path).exists() {
-        panic!("Directory does not exist! '{:?}'.", config.path);
+        return Err(PrebuiltError::Io(format!(
+            "Directory does not exist! '{:?}'.",
+            config.path
+        )));
     }
 
     // Only create/check reports path if needed.
     if !config.ci && !config.reports.is_empty() {
         if !config.no_create_path && create_dir_all(&config.report_path).is_err() {
-            panic!("Could not create the directory '{:?}'.", config.report_path);
+            return Err(PrebuiltError::Io(format!(
+                "Could not create the directory '{:?}'.",
+                config.report_path
+            )));
         }
         else if !Path::new(&config.report_path).exists() {
-            panic!("Directory does not exist! '{:?}'.", config.report_path);
+            return Err(PrebuiltError::Io(format!(
+                "Directory does not exist! '{:?}'.",
+                config.report_path
+            )));
         }
     }
 
-    // Build ureq agent
-    let agent = create_agent();
+    let mut state = State::load(config)?;
 
-    // Create Fetcher which is used to fetch items from index.
-    let mut fetcher = Fetcher::new(config, agent);
+    // With --atomic every crate is staged first, then committed together.
+    let mut batch = Stage::default();
+    let mut pending = Vec::new();
 
-    // Get pkgs
-    for pkg in config.pkgs.iter() {
-        let mut id = pkg.as_str();
-        let mut version = None; // None will pull the latest version
+    // With --jobs crates are resolved and downloaded ahead, but still installed in order.
+    let mut fetched = prefetch(config, fetcher, &state, pkgs).into_iter();
 
-        // If there is a version string get it
-        if let Some((i, j)) = id.split_once('@') {
-            id = i;
-            version = Some(j);
-        }
+    // Get pkgs
+    for pkg in pkgs.iter() {
+        let (id, version) = split_pkg(pkg);
 
         // If --get-latest then get latest version and print out latest event
         if config.get_latest {
-            events::get_latest(id, &fetcher.get_latest(id));
+            let latest = fetcher
+                .get_latest(id)
+                .map_err(|err| events::error(id, "", config, err))?;
+            events::get_latest(id, &latest);
             continue;
         }
 
-        // Get version that fetcher is using
-        let version = match version {
-            Some(v) => v.to_string(),
-            None => fetcher.get_latest(id),
+        let (version, download) = match fetched.next().flatten() {
+            Some((res, held)) => {
+                events::replay(held);
+                let (version, download) = res?;
+                (version, Some(download))
+            }
+            None => (resolve(config, fetcher, &state, id, version)?, None),
+        };
+
+        // Keep the installed version, so it can be rolled back to.
+        if let Some(installed) = state.crates().get(id) {
+            if installed.version.ne(&version) {
+                store::keep(config, id, installed)
+                    .map_err(|err| events::error(id, &version, config, err))?;
+            }
+        }
+
+        let download = match download {
+            Some(download) => download,
+            None => fetch_crate(config, fetcher, id, &version)
+                .map_err(|err| events::error(id, &version, config, err))?,
         };
-        let version = &version;
-
-        events::target(id, version, config);
-
-        // Download and hash tar
-        let (info, _hashes, tar_bytes) = fetcher.download(id, version, config);
-        let info = &info;
-
-        // Extract Tar
-        let reader = std::io::Cursor::new(tar_bytes);
-        let mut archive = Archive::new(GzDecoder::new(reader));
-        match archive.entries() {
-            Ok(es) => {
-                eprintln!(
-                    "{} {id}@{version}...",
-                    err_color_print("Extracting", PossibleColor::BrightBlue)
-                );
-
-                for e in es {
-                    let mut e = e.expect("Malformed entry in tarball.");
-
-                    let mut blob_data = Vec::new();
-                    e.read_to_end(&mut blob_data)
-                        .expect("Could not extract binary from archive.");
-
-                    let bin_path = e.path().expect("Could not extract path from archive.");
-                    let str_name = bin_path
-                        .clone()
-                        .into_owned()
-                        .into_os_string()
-                        .into_string()
-                        .expect("Archive has non utf-8 path.");
-
-                    // Make sure there are no path separators since this will be appended
-                    if str_name.contains(std::path::is_separator) {
-                        panic!(
-                            "{} path separator in archive for {id}@{version}",
-                            err_color_print("Illegal", PossibleColor::BrightRed)
-                        );
-                    }
 
-                    if !fetcher.is_bin(info, &str_name) {
-                        panic!(
-                            "{} binary ({str_name}) in archive for {id}@{version}",
-                            err_color_print("Illegal", PossibleColor::BrightRed)
-                        );
-                    }
+        if config.atomic {
+            let staged = stage_crate(config, fetcher, &mut batch, id, &version, download)
+                .map_err(|err| events::error(id, &version, config, err))?;
+            pending.push(staged);
+        }
+        else {
+            install_crate(config, fetcher, &mut state, id, &version, download)
+                .map_err(|err| events::error(id, &version, config, err))?;
+        }
+    }
+
+    if !pending.is_empty() {
+        // Every crate is installed, or the replaced binaries are put back.
+        if let Err(err) = batch.commit() {
+            for staged in pending.iter() {
+                events::failed(&staged.id, &staged.version, config, &err);
+            }
+            return Err(err);
+        }
+
+        // Every committed crate is recorded before any reports are fetched, so a failed report cannot leave one out.
+        for staged in pending.iter() {
+            record_crate(config, fetcher, &mut state, staged);
+        }
 
-                    let mut path = config.path.clone();
-                    path.push(bin_path);
+        let mut failed = None;
+        for staged in pending.iter() {
+            if let Err(err) = report_crate(config, fetcher, staged) {
+                let err = events::error(&staged.id, &staged.version, config, err);
+                failed.get_or_insert(err);
+            }
+        }
+        if let Some(err) = failed {
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
 
-                    if config.safe && !config.ci && path.exists() {
-                        panic!(
-                            "Binary {str_name} {} for {id}@{version}",
-                            err_color_print("already exists", PossibleColor::BrightRed)
-                        );
+/// Split a pkg into its id and version, None will pull the latest version.
+fn split_pkg(pkg: &str) -> (&str, Option<&str>) {
+    match pkg.split_once('@') {
+        Some((id, version)) => (id, Some(version)),
+        None => (pkg, None),
+    }
+}
+
+/// Get version that fetcher is using, held crates use their pinned version.
+fn resolve(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    state: &State,
+    id: &str,
+    version: Option<&str>,
+) -> Result<String, PrebuiltError> {
+    match (version, state.pinned(config, id)) {
+        (Some(v), _) => Ok(v.to_string()),
+        (None, Some(pin)) => {
+            eprintln!(
+                "{} {id}@{pin}.",
+                err_color_print("Using pinned", PossibleColor::BrightBlue)
+            );
+            Ok(pin.to_string())
+        }
+        (None, None) => fetcher
+            .get_latest(id)
+            .map_err(|err| events::error(id, "", config, err)),
+    }
+}
+
+/// A result from another thread, with the events it held back.
+type Held<T, E> = (Result<T, E>, Vec<String>);
+
+/// A crate resolved and downloaded on another thread.
+type Fetched = Held<(String, Download), PrebuiltError>;
+
+/// Resolve and download pkgs on --jobs threads, in the same position as pkgs.
+fn prefetch(
+    config: &Config,
+    fetcher: &Fetcher,
+    state: &State,
+    pkgs: &IndexSet<String>,
+) -> Vec<Option<Fetched>> {
+    if config.jobs <= 1 || config.get_latest || pkgs.len() <= 1 {
+        return Vec::new();
+    }
+
+    let pkgs: Vec<&str> = pkgs.iter().map(|pkg| pkg.as_str()).collect();
+    let agent = fetcher.agent();
+    in_order(
+        config.jobs,
+        pkgs.len(),
+        // Interacts cannot be shared, so each thread has its own fetcher.
+        || Fetcher::quiet(config, agent.clone()).ok(),
+        |fetcher, i| {
+            let (id, version) = split_pkg(pkgs[i]);
+            let version = resolve(config, fetcher, state, id, version)?;
+            let download = fetch_crate(config, fetcher, id, &version)
+                .map_err(|err| events::error(id, &version, config, err))?;
+            Ok((version, download))
+        },
+    )
+}
+
+/// Run work for 0..count on up to jobs threads, each with its own state from init.
+/// Results and their held events are kept in input order, no matter which finishes first.
+/// Items after one that failed are skipped, since they would not be used.
+fn in_order<S, T: Send, E: Send>(
+    jobs: usize,
+    count: usize,
+    init: impl Fn() -> Option<S> + Sync,
+    work: impl Fn(&mut S, usize) -> Result<T, E> + Sync,
+) -> Vec<Option<Held<T, E>>> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(usize::MAX);
+    let done = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|s| {
+        for _ in 0..jobs.min(count) {
+            s.spawn(|| {
+                let Some(mut state) = init()
+                else {
+                    return;
+                };
+
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= count || i > failed.load(Ordering::SeqCst) {
+                        return;
                     }
 
-                    let mut file =
-                        File::create(&path).expect("Could not open file to write binary to.");
-                    file.write_all(&blob_data)
-                        .expect("Could not write binary to file.");
-
-                    // Add +x permission on unix platforms.
-                    #[cfg(target_family = "unix")]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
-                            .expect("Could not set permissions.");
+                    let item = events::hold(|| work(&mut state, i));
+                    if item.0.is_err() {
+                        failed.fetch_min(i, Ordering::SeqCst);
                     }
+                    if let Ok(mut done) = done.lock() {
+                        done[i] = Some(item);
+                    }
+                }
+            });
+        }
+    });
 
-                    let abs = fs::canonicalize(path).expect("Could not canonicalize install path.");
+    done.into_inner().unwrap_or_default()
+}
 
-                    eprintln!(
-                        "{} {abs:?}.",
-                        err_color_print("Installed", PossibleColor::BrightPurple)
-                    );
+/// A verified archive, and the index that served it.
+struct Download {
+    info: InfoFileImm,
+    hashes: HashesFileImm,
+    tar: Vec<u8>,
+    index: usize,
+}
 
-                    events::binary_installed(id, version, config, abs.as_path());
-                }
-            }
-            Err(_) => panic!("Cannot get entries from downloaded tar."),
+fn fetch_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    id: &str,
+    version: &str,
+) -> Result<Download, PrebuiltError> {
+    events::target(id, version, config);
+
+    // Download and hash tar
+    let (info, hashes, tar) = fetcher.download(id, version, config)?;
+
+    Ok(Download {
+        info,
+        hashes,
+        tar,
+        index: fetcher.current(),
+    })
+}
+
+/// A crate whose binaries are staged, waiting for them to be committed.
+struct StagedCrate {
+    id: String,
+    version: String,
+    info: InfoFileImm,
+    index: usize,                 // Index that served the crate
+    bins: Vec<(PathBuf, Hashes)>, // Install path and hashes of each binary
+}
+
+fn install_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    state: &mut State,
+    id: &str,
+    version: &str,
+    download: Download,
+) -> Result<(), PrebuiltError> {
+    let mut stage = Stage::default();
+    let staged = stage_crate(config, fetcher, &mut stage, id, version, download)?;
+
+    // Every binary of a crate is installed, or none are.
+    stage.commit()?;
+
+    finish_crate(config, fetcher, state, staged)
+}
+
+/// Extract, verify, and stage the binaries of a crate.
+fn stage_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    stage: &mut Stage,
+    id: &str,
+    version: &str,
+    download: Download,
+) -> Result<StagedCrate, PrebuiltError> {
+    let Download {
+        info,
+        hashes,
+        tar,
+        index,
+    } = download;
+
+    // Extract Tar
+    let reader = std::io::Cursor::new(tar);
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    let mut bins = Vec::new();
+    let mut extracted = Vec::new();
+    let es = archive.entries().map_err(|_| {
+        PrebuiltError::Malformed(format!(
+            "Cannot get entries from downloaded tar for {id}@{version}."
+        ))
+    })?;
+
+    eprintln!(
+        "{} {id}@{version}...",
+        err_color_print("Extracting", PossibleColor::BrightBlue)
+    );
+
+    for e in es {
+        let malformed =
+            |item: &str| PrebuiltError::Malformed(format!("{item} in archive for {id}@{version}."));
+        let mut e = e.map_err(|_| malformed("Malformed entry"))?;
+
+        let mut blob_data = Vec::new();
+        e.read_to_end(&mut blob_data)
+            .map_err(|_| malformed("Could not extract binary"))?;
+
+        let bin_path = e.path().map_err(|_| malformed("Malformed path"))?;
+        let str_name = bin_path
+            .clone()
+            .into_owned()
+            .into_os_string()
+            .into_string()
+            .map_err(|_| malformed("Non utf-8 path"))?;
+
+        // Make sure there are no path separators since this will be appended
+        if str_name.contains(std::path::is_separator) {
+            return Err(malformed("Illegal path separator"));
         }
 
-        // Reports
-        if !config.ci {
-            fetcher.reports(id, version, info, config);
+        if !fetcher.is_bin(&info, &str_name) {
+            return Err(malformed(&format!("Illegal binary ({str_name})")));
         }
 
+        fetcher.verify_bin(id, version, config, &hashes, &str_name, &blob_data)?;
+
+        let mut path = config.path.clone();
+        path.push(bin_path);
+
+        if config.safe && !config.ci && path.exists() {
+            return Err(PrebuiltError::AlreadyExists(format!(
+                "Binary {str_name} already exists for {id}@{version}."
+            )));
+        }
+
+        // Hashed with the first allowed hash, to find changed binaries later.
+        let bin_hashes: Hashes = config
+            .hashes
+            .first()
+            .map(|t| (*t, t.digest(&blob_data)))
+            .into_iter()
+            .collect();
+
+        bins.push((path.clone(), bin_hashes.clone()));
+        extracted.push((
+            InstalledBinV1 {
+                path,
+                hashes: bin_hashes,
+            },
+            blob_data,
+        ));
+    }
+
+    if config.store {
+        store::link(config, stage, id, &extracted)?;
+    }
+    else {
+        for (bin, bytes) in extracted {
+            stage.add(bin.path, &bytes)?;
+        }
+    }
+
+    Ok(StagedCrate {
+        id: id.to_string(),
+        version: version.to_string(),
+        info,
+        index,
+        bins,
+    })
+}
+
+/// Record, and get reports for, a crate after its binaries were committed.
+fn finish_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    state: &mut State,
+    staged: StagedCrate,
+) -> Result<(), PrebuiltError> {
+    record_crate(config, fetcher, state, &staged);
+    report_crate(config, fetcher, &staged)
+}
+
+/// Add a committed crate to the state file.
+fn record_crate(config: &Config, fetcher: &mut Fetcher, state: &mut State, staged: &StagedCrate) {
+    let (id, version) = (staged.id.as_str(), staged.version.as_str());
+    fetcher.set_current(staged.index);
+
+    let mut bins = Vec::new();
+    for (path, hashes) in staged.bins.iter() {
+        // Only the directory, a link from --store should not resolve into the store.
+        let abs = path
+            .parent()
+            .and_then(|dir| fs::canonicalize(dir).ok())
+            .zip(path.file_name())
+            .map(|(dir, name)| dir.join(name))
+            .unwrap_or_else(|| path.clone());
+
         eprintln!(
-            "{} {id}@{version}.",
-            err_color_print("Installed", PossibleColor::BrightGreen)
+            "{} {abs:?}.",
+            err_color_print("Installed", PossibleColor::BrightPurple)
         );
-        events::installed(id, version, config);
+
+        events::binary_installed(id, version, config, abs.as_path());
+        bins.push(InstalledBinV1 {
+            path: abs,
+            hashes: hashes.clone(),
+        });
     }
 
-    eprintln!("{}", err_color_print("Done!", PossibleColor::Green));
+    let previous = match state.crates().get(id) {
+        Some(old) if old.version.ne(version) && store::contains(config, id, old) => {
+            Some(Box::new(InstalledCrateV1 {
+                previous: None,
+                ..old.clone()
+            }))
+        }
+        // Reinstalling the same version keeps what it replaced.
+        Some(old) if old.version.eq(version) => old.previous.clone(),
+        _ => None,
+    };
+
+    state.insert(
+        id,
+        InstalledCrateV1 {
+            version: version.to_string(),
+            index: fetcher.index().to_string(),
+            target: config.target.clone(),
+            installed: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            bins,
+            previous,
+        },
+    );
+    // The binaries are already installed, so only warn.
+    match state.save() {
+        Ok(_) => {
+            if let Some(installed) = state.crates().get(id) {
+                store::prune(config, id, installed);
+            }
+        }
+        Err(err) => eprintln!(
+            "{} {err}",
+            err_color_print("Warning:", PossibleColor::BrightYellow)
+        ),
+    }
+}
 
-    Ok(())
+/// Get reports for a recorded crate, it is installed even if they fail.
+fn report_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    staged: &StagedCrate,
+) -> Result<(), PrebuiltError> {
+    let (id, version) = (staged.id.as_str(), staged.version.as_str());
+    fetcher.set_current(staged.index);
+
+    let reports = match config.ci {
+        true => Ok(()),
+        false => fetcher.reports(id, version, &staged.info, config),
+    };
+
+    eprintln!(
+        "{} {id}@{version}.",
+        err_color_print("Installed", PossibleColor::BrightGreen)
+    );
+    events::installed(id, version, config);
+
+    reports
 }
 
-fn should_error() {
+fn should_error() -> Result<(), PrebuiltError> {
     // No TLS
     #[cfg(not(any(feature = "native", feature = "rustls")))]
-    panic!("cargo-prebuilt only supports https and was built without the 'native' or 'rustls' feature.");
+    return Err(PrebuiltError::Unsupported("cargo-prebuilt only supports https and was built without the 'native' or 'rustls' feature.".to_string()));
 
     // No Indexes
-    #[cfg(not(any(feature = "github-public", feature = "github-private")))]
-    panic!("cargo-prebuilt was not built with any indexes, try the 'indexes' feature.");
+    #[cfg(not(any(
+        feature = "github-public",
+        feature = "github-private",
+        feature = "gitlab-public",
+        feature = "gitlab-private",
+        feature = "forgejo-public",
+        feature = "forgejo-private",
+        feature = "custom-http-public",
+        feature = "custom-http-private",
+        feature = "file"
+    )))]
+    return Err(PrebuiltError::Unsupported(
+        "cargo-prebuilt was not built with any indexes, try the 'indexes' feature.".to_string(),
+    ));
+
+    #[allow(unreachable_code)]
+    Ok(())
 }
 
-fn create_agent() -> ureq::Agent {
+fn create_agent() -> Result<ureq::Agent, PrebuiltError> {
     #[cfg(feature = "native")]
     let agent = ureq::AgentBuilder::new().tls_connector(std::sync::Arc::new(
-        native_tls::TlsConnector::new().expect("Could not create TlsConnector"),
+        native_tls::TlsConnector::new()
+            .map_err(|err| PrebuiltError::Io(format!("Could not create TlsConnector.\n{err}")))?,
     ));
 
     #[cfg(feature = "rustls")]
@@ -214,5 +641,194 @@ fn create_agent() -> ureq::Agent {
     #[cfg(not(any(feature = "native", feature = "rustls")))]
     let agent = ureq::agent();
 
-    agent
+    Ok(agent)
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::{write::GzEncoder, Compression};
+    use std::{fs, path::Path};
+
+    use std::{sync::Mutex, thread, time::Duration};
+
+    use super::{create_agent, in_order, install, run_mode, split_pkg};
+    use crate::{config::test_config, data::HashType, events, get::Fetcher, state::State};
+
+    /// Add a crate version with one binary to a file index, and make it the latest.
+    /// bin_hash overrides the hash of the binary.
+    pub fn index_crate(
+        root: &Path,
+        target: &str,
+        id: &str,
+        version: &str,
+        bytes: &[u8],
+        bin_hash: Option<&str>,
+    ) {
+        let dir = root.join(format!("{id}-{version}"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(root.join("stable-index")).unwrap();
+        fs::write(root.join("stable-index").join(id), format!("{version}\n")).unwrap();
+
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        tar.append_data(&mut header, id, bytes).unwrap();
+        let tar = tar.into_inner().unwrap().finish().unwrap();
+        fs::write(dir.join(format!("{target}.tar.gz")), &tar).unwrap();
+
+        let sha = |bytes: &[u8]| HashType::Sha256.digest(bytes);
+        let bin_hash = bin_hash.map_or_else(|| sha(bytes), |h| h.to_string());
+        fs::write(
+            dir.join("info.json"),
+            format!(
+                r#"{{"info_version":"1","id":"{id}","version":"{version}","license":"MIT","git":"x","description":"x","bins":["{id}"],"info":{{}},"archive":{{"compression":"gz","ext":"tar.gz"}},"files":{{"hash":"hashes.json","license":"license.report","deps":"deps.report","audit":"audit.report"}},"targets":["{target}"]}}"#
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hashes.json"),
+            format!(
+                r#"{{"hashes_version":"1","hashes":{{"{target}":{{"archive":{{"sha256":"{}"}},"bins":{{"{id}":{{"sha256":"{bin_hash}"}}}}}}}}}}"#,
+                sha(&tar)
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_atomic() {
+        let root = std::env::temp_dir().join(format!("prebuilt-atomic-{}", std::process::id()));
+        let index = root.join("index");
+        let index_arg = format!("--index=file:{}", index.display());
+        let config = test_config(&root, &["--atomic", &index_arg, "alpha,broken"]);
+        index_crate(&index, &config.target, "alpha", "1.0.0", b"new", None);
+        index_crate(
+            &index,
+            &config.target,
+            "broken",
+            "1.0.0",
+            b"bin",
+            Some("00"),
+        );
+        fs::create_dir_all(&config.path).unwrap();
+        fs::write(config.path.join("alpha"), b"old").unwrap();
+
+        // A crate failing after alpha was staged leaves alpha untouched
+        let mut fetcher = Fetcher::new(&config, create_agent().unwrap()).unwrap();
+        assert!(install(&config, &mut fetcher, &config.pkgs).is_err());
+        assert_eq!(fs::read(config.path.join("alpha")).unwrap(), b"old");
+        assert!(!config.path.join("broken").exists());
+        assert!(State::load(&config).unwrap().crates().is_empty());
+
+        // A failed commit is an error for every staged crate
+        let config = test_config(&root, &["--atomic", "--out", &index_arg, "alpha"]);
+        fs::remove_file(config.path.join("alpha")).unwrap();
+        fs::create_dir_all(config.path.join("alpha/taken")).unwrap();
+        let (res, held) = events::hold(|| install(&config, &mut fetcher, &config.pkgs));
+        assert!(res.is_err());
+        assert!(held.iter().any(|e| e.contains(r#""crate":"alpha""#)
+            && e.contains(r#""event":"error""#)
+            && e.contains(r#""kind":"io""#)));
+        fs::remove_dir_all(config.path.join("alpha")).unwrap();
+        fs::write(config.path.join("alpha"), b"old").unwrap();
+
+        // Without it, every crate before the failure is installed
+        let config = test_config(&root, &[&index_arg, "alpha,broken"]);
+        assert!(install(&config, &mut fetcher, &config.pkgs).is_err());
+        assert_eq!(fs::read(config.path.join("alpha")).unwrap(), b"new");
+        assert!(State::load(&config).unwrap().crates().contains_key("alpha"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_in_order() {
+        // The first item finishes last, but is still first with its own events
+        let finished = Mutex::new(Vec::new());
+        let done = in_order(
+            2,
+            3,
+            || Some(()),
+            |_, i| {
+                if i == 0 {
+                    thread::sleep(Duration::from_millis(200));
+                }
+                events::get_latest(&i.to_string(), "1.0.0");
+                finished.lock().unwrap().push(i);
+                Ok::<_, ()>(i)
+            },
+        );
+        assert_eq!(finished.into_inner().unwrap().last(), Some(&0));
+        for (i, item) in done.into_iter().enumerate() {
+            let (res, held) = item.unwrap();
+            assert_eq!(res, Ok(i));
+            assert_eq!(held.len(), 1);
+            assert!(held[0].contains(&format!(r#""crate":"{i}""#)));
+        }
+
+        // Items after a failure are skipped
+        let done = in_order(
+            1,
+            3,
+            || Some(()),
+            |_, i| if i == 1 { Err(i) } else { Ok(i) },
+        );
+        assert!(matches!(done[1], Some((Err(1), _))));
+        assert!(done[2].is_none());
+    }
+
+    #[test]
+    fn test_store_pruned() {
+        let root = std::env::temp_dir().join(format!("prebuilt-pruned-{}", std::process::id()));
+        let index = root.join("index");
+        let index_arg = format!("--index=file:{}", index.display());
+
+        // Only the version replaced last is kept
+        for version in ["1.0.0", "1.1.0", "1.2.0"] {
+            let pkg = format!("demo@{version}");
+            let config = test_config(&root, &[&index_arg, &pkg]);
+            index_crate(
+                &index,
+                &config.target,
+                "demo",
+                version,
+                version.as_bytes(),
+                None,
+            );
+            let mut fetcher = Fetcher::new(&config, create_agent().unwrap()).unwrap();
+            install(&config, &mut fetcher, &config.pkgs).unwrap();
+        }
+        let kept: Vec<_> = fs::read_dir(root.join("store/demo"))
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .collect();
+        assert_eq!(kept, vec![1]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_unreported() {
+        let root = std::env::temp_dir().join(format!("prebuilt-unreported-{}", std::process::id()));
+        let config = test_config(&root, &["--out", "list"]);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&config.state_path, b"{").unwrap();
+
+        // Failures that are not about a crate still have an error event
+        let (res, held) = events::hold(|| run_mode(&config));
+        assert!(res.is_err());
+        assert_eq!(held.len(), 1);
+        assert!(held[0].contains(r#""kind":"malformed""#));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_split_pkg() {
+        assert_eq!(split_pkg("ripgrep"), ("ripgrep", None));
+        assert_eq!(split_pkg("ripgrep@14.1.0"), ("ripgrep", Some("14.1.0")));
+        assert_eq!(split_pkg("ripgrep@"), ("ripgrep", Some("")));
+    }
 }
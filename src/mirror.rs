@@ -0,0 +1,113 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::Path,
+};
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    error::PrebuiltError,
+    events,
+    get::Fetcher,
+};
+
+/// Copy crates from the index into dir, laid out so it can be used as a file: index.
+pub fn mirror(config: &Config, fetcher: &mut Fetcher, dir: &Path) -> Result<(), PrebuiltError> {
+    if !config.no_create_path && create_dir_all(dir).is_err() {
+        return Err(PrebuiltError::Io(format!(
+            "Could not create the directory '{dir:?}'."
+        )));
+    }
+    else if !dir.exists() {
+        return Err(PrebuiltError::Io(format!(
+            "Directory does not exist! '{dir:?}'."
+        )));
+    }
+
+    // Mirror everything if no crates were passed
+    let pkgs: Vec<String> = if config.pkgs.is_empty() {
+        fetcher.get_ids()?
+    }
+    else {
+        config.pkgs.iter().cloned().collect()
+    };
+
+    for pkg in pkgs.iter() {
+        // Without a version the latest one is pulled.
+        let (id, version) = crate::split_pkg(pkg);
+
+        // Only the latest version is added to the stable index of the mirror
+        let latest = version.is_none();
+        let version = match version {
+            Some(v) => v.to_string(),
+            None => fetcher
+                .get_latest(id)
+                .map_err(|err| events::error(id, "", config, err))?,
+        };
+
+        mirror_crate(config, fetcher, dir, id, &version, latest)
+            .map_err(|err| events::error(id, &version, config, err))?;
+    }
+
+    Ok(())
+}
+
+fn mirror_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    dir: &Path,
+    id: &str,
+    version: &str,
+    latest: bool,
+) -> Result<(), PrebuiltError> {
+    check_name(id, version, id)?;
+    check_name(id, version, version)?;
+
+    let files = fetcher.mirror(id, version, config)?;
+
+    let mut crate_dir = dir.to_path_buf();
+    crate_dir.push(format!("{id}-{version}"));
+    create_dir_all(&crate_dir).map_err(|_| {
+        PrebuiltError::Io(format!("Could not create the directory '{crate_dir:?}'."))
+    })?;
+
+    for (name, bytes) in files {
+        check_name(id, version, &name)?;
+
+        let mut path = crate_dir.clone();
+        path.push(&name);
+        fs::write(&path, bytes)
+            .map_err(|_| PrebuiltError::Io(format!("Could not write {path:?}.")))?;
+    }
+
+    // Written last, so a failed mirror never points to missing files.
+    if latest {
+        let mut path = dir.to_path_buf();
+        path.push("stable-index");
+        create_dir_all(&path).map_err(|_| {
+            PrebuiltError::Io(format!("Could not create the directory '{path:?}'."))
+        })?;
+        path.push(id);
+        fs::write(&path, version)
+            .map_err(|_| PrebuiltError::Io(format!("Could not write {path:?}.")))?;
+    }
+
+    eprintln!(
+        "{} {id}@{version} to {crate_dir:?}.",
+        err_color_print("Mirrored", PossibleColor::BrightGreen)
+    );
+    events::mirrored(id, version, config, &crate_dir);
+
+    Ok(())
+}
+
+fn check_name(id: &str, version: &str, name: &str) -> Result<(), PrebuiltError> {
+    // Make sure there are no path separators since this will be appended
+    if name.is_empty() || name.eq("..") || name.contains(std::path::is_separator) {
+        return Err(PrebuiltError::Malformed(format!(
+            "Illegal file name {name:?} for {id}@{version}."
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    error::PrebuiltError,
+    events,
+    state::State,
+};
+
+/// Hold crates at a version, so installs without a version and upgrades use it.
+pub fn pin(config: &Config) -> Result<(), PrebuiltError> {
+    let mut state = State::load(config)?;
+
+    for pkg in config.pkgs.iter() {
+        let (id, Some(version)) = crate::split_pkg(pkg)
+        else {
+            return Err(PrebuiltError::Config(format!(
+                "A version is needed to pin {pkg}. (EX: {pkg}@1.0.0)"
+            )));
+        };
+
+        state.pin(id, version);
+        eprintln!(
+            "{} {id}@{version}.",
+            err_color_print("Pinned", PossibleColor::BrightGreen)
+        );
+        events::pinned(id, version, config);
+    }
+
+    state.save()
+}
+
+/// Stop holding crates at a version.
+pub fn unpin(config: &Config) -> Result<(), PrebuiltError> {
+    let mut state = State::load(config)?;
+
+    for pkg in config.pkgs.iter() {
+        // Allow the same CSV that was used for pin.
+        let (id, _) = crate::split_pkg(pkg);
+
+        match state.unpin(id) {
+            Some(version) => {
+                eprintln!(
+                    "{} {id}@{version}.",
+                    err_color_print("Unpinned", PossibleColor::BrightGreen)
+                );
+                events::unpinned(id, &version, config);
+            }
+            None => eprintln!("{id} is not pinned."),
+        }
+
+        if let Some(version) = config.pins.get(id) {
+            eprintln!(
+                "{id} is still pinned to {version} by {} of the config file.",
+                err_color_print("[pins]", PossibleColor::BrightYellow)
+            );
+        }
+    }
+
+    state.save()
+}
@@ -0,0 +1,187 @@
+use std::fs;
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    data::{InstalledBinV1, InstalledCrateV1},
+    error::PrebuiltError,
+    events,
+    stage::Stage,
+    state::{self, BinStatus, State},
+    store,
+};
+
+/// Swap crates back to the version they had before their last upgrade.
+pub fn rollback(config: &Config) -> Result<(), PrebuiltError> {
+    let mut state = State::load(config)?;
+
+    for pkg in config.pkgs.iter() {
+        let (id, _) = crate::split_pkg(pkg);
+        rollback_crate(config, &mut state, id).map_err(|err| events::error(id, "", config, err))?;
+    }
+
+    Ok(())
+}
+
+fn rollback_crate(config: &Config, state: &mut State, id: &str) -> Result<(), PrebuiltError> {
+    let Some(current) = state.crates().get(id).cloned()
+    else {
+        return Err(PrebuiltError::NotFound(format!(
+            "{id} is not installed by cargo-prebuilt."
+        )));
+    };
+    let Some(previous) = current.previous.clone()
+    else {
+        return Err(PrebuiltError::NotFound(format!(
+            "No previous version of {id} to roll back to."
+        )));
+    };
+    let mut previous = *previous;
+
+    // Keep the current version, so the rollback can be undone.
+    let kept = store::keep(config, id, &current)?;
+
+    let mut stage = Stage::default();
+    for bin in previous.bins.iter() {
+        let from = store::file(config, id, bin)?;
+
+        // The store is checked like an installed binary.
+        let stored = InstalledBinV1 {
+            path: from.clone(),
+            hashes: bin.hashes.clone(),
+        };
+        match state::bin_status(&stored) {
+            BinStatus::Ok => {}
+            BinStatus::Modified => {
+                return Err(PrebuiltError::HashMismatch(format!(
+                    "Kept binary {from:?} was changed."
+                )))
+            }
+            BinStatus::Missing => {
+                return Err(PrebuiltError::NotFound(format!(
+                    "Kept binary {from:?} is missing."
+                )))
+            }
+        }
+
+        if config.store {
+            let from = fs::canonicalize(&from)
+                .map_err(|_| PrebuiltError::Io(format!("Could not find {from:?}.")))?;
+            stage.link(bin.path.clone(), &from)?;
+        }
+        else {
+            let bytes = fs::read(&from)
+                .map_err(|_| PrebuiltError::Io(format!("Could not read {from:?}.")))?;
+            stage.add(bin.path.clone(), &bytes)?;
+        }
+    }
+    stage.commit()?;
+
+    // Binaries that only the current version has.
+    for bin in current.bins.iter() {
+        if !previous.bins.iter().any(|b| b.path.eq(&bin.path))
+            && state::bin_status(bin) == BinStatus::Ok
+            && fs::remove_file(&bin.path).is_err()
+        {
+            eprintln!("Could not remove {:?}.", bin.path);
+        }
+    }
+
+    let from = current.version.clone();
+    previous.previous = kept.then(|| {
+        Box::new(InstalledCrateV1 {
+            previous: None,
+            ..current
+        })
+    });
+    let version = previous.version.clone();
+    state.insert(id, previous);
+    state.save()?;
+
+    eprintln!(
+        "{} {id}@{from} to {version}.",
+        err_color_print("Rolled back", PossibleColor::BrightGreen)
+    );
+    events::rolled_back(id, &version, config);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use super::rollback_crate;
+    use crate::{
+        config::test_config,
+        data::{HashType, InstalledBinV1, InstalledCrateV1},
+        state::State,
+        store,
+    };
+
+    fn bin(path: &Path, bytes: &[u8]) -> InstalledBinV1 {
+        fs::write(path, bytes).unwrap();
+        InstalledBinV1 {
+            path: path.to_path_buf(),
+            hashes: HashMap::from([(HashType::Sha256, HashType::Sha256.digest(bytes))]),
+        }
+    }
+
+    #[test]
+    fn test_rollback() {
+        let root = std::env::temp_dir().join(format!("prebuilt-rollback-{}", std::process::id()));
+        let config = test_config(&root, &["rollback", "demo"]);
+        fs::create_dir_all(&config.path).unwrap();
+        let demo = config.path.join("demo");
+        let extra = config.path.join("demo-extra");
+
+        // 1.0.0 is kept when 1.1.0 replaces it, which adds a binary
+        let old = InstalledCrateV1 {
+            version: "1.0.0".to_string(),
+            index: "file:index".to_string(),
+            target: config.target.clone(),
+            installed: 0,
+            bins: vec![bin(&demo, b"v1")],
+            previous: None,
+        };
+        assert!(store::keep(&config, "demo", &old).unwrap());
+        let new = InstalledCrateV1 {
+            version: "1.1.0".to_string(),
+            bins: vec![bin(&demo, b"v2"), bin(&extra, b"extra")],
+            previous: Some(Box::new(old.clone())),
+            ..old
+        };
+        let mut state = State::load(&config).unwrap();
+        state.insert("demo", new);
+        state.save().unwrap();
+
+        // The previous binaries and state are restored
+        rollback_crate(&config, &mut state, "demo").unwrap();
+        assert_eq!(fs::read(&demo).unwrap(), b"v1");
+        assert!(!extra.exists());
+        let state = State::load(&config).unwrap();
+        let installed = &state.crates()["demo"];
+        assert_eq!(installed.version, "1.0.0");
+        assert_eq!(installed.previous.as_ref().unwrap().version, "1.1.0");
+
+        // Rolling back again undoes it
+        let mut state = state;
+        rollback_crate(&config, &mut state, "demo").unwrap();
+        assert_eq!(fs::read(&demo).unwrap(), b"v2");
+        assert_eq!(fs::read(&extra).unwrap(), b"extra");
+        assert_eq!(state.crates()["demo"].version, "1.1.0");
+
+        // Changed kept binaries are never rolled back to
+        let kept = store::file(
+            &config,
+            "demo",
+            &state.crates()["demo"].previous.as_ref().unwrap().bins[0],
+        )
+        .unwrap();
+        fs::write(kept, b"changed").unwrap();
+        assert!(rollback_crate(&config, &mut state, "demo").is_err());
+        assert_eq!(fs::read(&demo).unwrap(), b"v2");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
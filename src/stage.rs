@@ -0,0 +1,238 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error::PrebuiltError;
+
+struct StagedBin {
+    tmp: PathBuf,
+    path: PathBuf,
+    backup: Option<PathBuf>, // Binary that was replaced
+    swapped: bool,
+}
+
+/// Binaries written next to where they will be installed, so they can be swapped in together.
+#[derive(Default)]
+pub struct Stage {
+    bins: Vec<StagedBin>,
+    committed: bool,
+}
+impl Stage {
+    /// Write, sync, and set permissions for a binary, without touching path.
+    pub fn add(&mut self, path: PathBuf, bytes: &[u8]) -> Result<(), PrebuiltError> {
+        let tmp = self.push(path)?;
+        let io = |item: &str| PrebuiltError::Io(format!("{item} {tmp:?}."));
+
+        let mut file = File::create(&tmp).map_err(|_| io("Could not open"))?;
+        file.write_all(bytes)
+            .map_err(|_| io("Could not write binary to"))?;
+        file.sync_all().map_err(|_| io("Could not sync"))?;
+
+        // Add +x permission on unix platforms.
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))
+                .map_err(|_| io("Could not set permissions for"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Link a binary to a file in the store, without touching path.
+    /// Falls back to a hardlink, then a copy, where symlinks cannot be made.
+    pub fn link(&mut self, path: PathBuf, to: &Path) -> Result<(), PrebuiltError> {
+        let tmp = self.push(path)?;
+
+        if !symlink(to, &tmp) && fs::hard_link(to, &tmp).is_err() {
+            fs::copy(to, &tmp)
+                .map_err(|_| PrebuiltError::Io(format!("Could not link {to:?} to {tmp:?}.")))?;
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, path: PathBuf) -> Result<PathBuf, PrebuiltError> {
+        // Two crates in one batch would fight over the same temp file.
+        if self.bins.iter().any(|bin| bin.path.eq(&path)) {
+            return Err(PrebuiltError::AlreadyExists(format!(
+                "Binary {path:?} would be installed twice."
+            )));
+        }
+
+        // A temp file left by a crash could be a link, which would be written through.
+        let tmp = sibling(&path, "part");
+        let _ = fs::remove_file(&tmp);
+
+        // Added first, so it is removed even if writing fails.
+        self.bins.push(StagedBin {
+            tmp: tmp.clone(),
+            path,
+            backup: None,
+            swapped: false,
+        });
+
+        Ok(tmp)
+    }
+
+    /// Rename every binary into place, putting back what was replaced if any of them fail.
+    pub fn commit(&mut self) -> Result<(), PrebuiltError> {
+        for i in 0..self.bins.len() {
+            if let Err(err) = self.swap(i) {
+                self.undo();
+                self.sync_dirs();
+                return Err(err);
+            }
+        }
+        self.committed = true;
+
+        // Replaced binaries are not needed anymore.
+        for bin in self.bins.iter_mut() {
+            if let Some(backup) = bin.backup.take() {
+                let _ = fs::remove_file(backup);
+            }
+        }
+        self.sync_dirs();
+
+        Ok(())
+    }
+
+    fn swap(&mut self, i: usize) -> Result<(), PrebuiltError> {
+        let bin = &mut self.bins[i];
+        let io = |item: &str, path: &Path| PrebuiltError::Io(format!("{item} {path:?}."));
+
+        // A second name for the old binary, so it can be put back without ever moving it away.
+        if fs::symlink_metadata(&bin.path).is_ok() {
+            let backup = sibling(&bin.path, "old");
+            let _ = fs::remove_file(&backup);
+            if fs::hard_link(&bin.path, &backup).is_err() {
+                fs::copy(&bin.path, &backup).map_err(|_| io("Could not back up", &bin.path))?;
+            }
+            bin.backup = Some(backup);
+        }
+
+        // Renamed over the binary, so path always exists. (Unlike writing over it, this works while it runs on unix)
+        fs::rename(&bin.tmp, &bin.path).map_err(|_| io("Could not install", &bin.path))?;
+        bin.swapped = true;
+
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        for bin in self.bins.iter_mut().rev() {
+            match (bin.swapped, bin.backup.take()) {
+                (true, Some(backup)) => {
+                    let _ = fs::rename(backup, &bin.path);
+                }
+                (true, None) => {
+                    let _ = fs::rename(&bin.path, &bin.tmp);
+                }
+                (false, Some(backup)) => {
+                    let _ = fs::remove_file(backup);
+                }
+                (false, None) => {}
+            }
+            bin.swapped = false;
+        }
+    }
+
+    /// Flush the renames to disk, so a crash cannot bring back the old directory entries.
+    fn sync_dirs(&self) {
+        #[cfg(target_family = "unix")]
+        {
+            let mut dirs: Vec<&Path> = self.bins.iter().filter_map(|b| b.path.parent()).collect();
+            dirs.sort();
+            dirs.dedup();
+            for dir in dirs {
+                if let Ok(dir) = File::open(dir) {
+                    let _ = dir.sync_all();
+                }
+            }
+        }
+    }
+}
+impl Drop for Stage {
+    fn drop(&mut self) {
+        if !self.committed {
+            for bin in self.bins.iter() {
+                let _ = fs::remove_file(&bin.tmp);
+            }
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn symlink(to: &Path, link: &Path) -> bool {
+    std::os::unix::fs::symlink(to, link).is_ok()
+}
+
+// Needs developer mode or admin rights.
+#[cfg(target_family = "windows")]
+fn symlink(to: &Path, link: &Path) -> bool {
+    std::os::windows::fs::symlink_file(to, link).is_ok()
+}
+
+#[cfg(not(any(target_family = "unix", target_family = "windows")))]
+fn symlink(_to: &Path, _link: &Path) -> bool {
+    false
+}
+
+/// Hidden file next to path, so renaming it is atomic.
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.prebuilt-{ext}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{sibling, Stage};
+
+    #[test]
+    fn test_stage() {
+        let dir = std::env::temp_dir().join(format!("prebuilt-stage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a");
+        let b = dir.join("b");
+        fs::write(&a, b"old a").unwrap();
+
+        // A failed commit puts back every replaced binary
+        let mut stage = Stage::default();
+        stage.add(a.clone(), b"new a").unwrap();
+        stage.add(b.clone(), b"new b").unwrap();
+        fs::remove_file(sibling(&b, "part")).unwrap();
+        assert!(stage.commit().is_err());
+        drop(stage);
+        assert_eq!(fs::read(&a).unwrap(), b"old a");
+        assert!(!b.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // A commit replaces every binary and leaves nothing behind
+        let mut stage = Stage::default();
+        stage.add(a.clone(), b"new a").unwrap();
+        stage.add(b.clone(), b"new b").unwrap();
+        stage.commit().unwrap();
+        drop(stage);
+        assert_eq!(fs::read(&a).unwrap(), b"new a");
+        assert_eq!(fs::read(&b).unwrap(), b"new b");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Links replace binaries the same way
+        let store = dir.join("store");
+        fs::write(&store, b"stored").unwrap();
+        let mut stage = Stage::default();
+        stage.link(a.clone(), &store).unwrap();
+        stage.commit().unwrap();
+        drop(stage);
+        assert_eq!(fs::read(&a).unwrap(), b"stored");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
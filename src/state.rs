@@ -0,0 +1,231 @@
+use std::{
+    fs::{self, create_dir_all},
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    config::{Config, IndexConfig},
+    data::{InstalledBinV1, InstalledCrateV1, StateFile, StateFileV1},
+    error::PrebuiltError,
+};
+
+/// Crates installed by cargo-prebuilt, stored at --state-path.
+pub struct State {
+    path: PathBuf,
+    file: StateFile,
+}
+impl State {
+    pub fn load(config: &Config) -> Result<Self, PrebuiltError> {
+        let path = config.state_path.clone();
+        let file = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw).map_err(|_| {
+                PrebuiltError::Malformed(format!("State file {path:?} is malformed."))
+            })?,
+            // Nothing has been installed yet.
+            Err(err) if err.kind() == ErrorKind::NotFound => StateFile::V1(StateFileV1::default()),
+            Err(_) => {
+                return Err(PrebuiltError::Io(format!(
+                    "Could not read state file {path:?}."
+                )))
+            }
+        };
+
+        Ok(Self { path, file })
+    }
+
+    pub fn crates(&self) -> &IndexMap<String, InstalledCrateV1> {
+        match &self.file {
+            StateFile::V1(file) => &file.crates,
+        }
+    }
+
+    fn crates_mut(&mut self) -> &mut IndexMap<String, InstalledCrateV1> {
+        match &mut self.file {
+            StateFile::V1(file) => &mut file.crates,
+        }
+    }
+
+    pub fn insert(&mut self, id: &str, installed: InstalledCrateV1) {
+        self.crates_mut().insert(id.to_string(), installed);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<InstalledCrateV1> {
+        self.crates_mut().shift_remove(id)
+    }
+
+    fn pins_mut(&mut self) -> &mut IndexMap<String, String> {
+        match &mut self.file {
+            StateFile::V1(file) => &mut file.pins,
+        }
+    }
+
+    pub fn pin(&mut self, id: &str, version: &str) {
+        self.pins_mut().insert(id.to_string(), version.to_string());
+    }
+
+    pub fn unpin(&mut self, id: &str) -> Option<String> {
+        self.pins_mut().shift_remove(id)
+    }
+
+    /// Version a crate is held at, pins from the pin command win over the config file.
+    pub fn pinned<'a>(&'a self, config: &'a Config, id: &str) -> Option<&'a str> {
+        let pins = match &self.file {
+            StateFile::V1(file) => &file.pins,
+        };
+        pins.get(id)
+            .or_else(|| config.pins.get(id))
+            .map(|v| v.as_str())
+    }
+
+    /// Written to a temp file first, so a crash never leaves a truncated state file.
+    pub fn save(&self) -> Result<(), PrebuiltError> {
+        let io = || PrebuiltError::Io(format!("Could not write state file {:?}.", self.path));
+
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).map_err(|_| io())?;
+        }
+
+        let raw = serde_json::to_string_pretty(&self.file).map_err(|_| io())?;
+
+        let tmp = self.path.with_extension("json.part");
+        fs::write(&tmp, raw)
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|_| {
+                let _ = fs::remove_file(&tmp);
+                io()
+            })
+    }
+}
+
+/// Configured index a crate was installed from, crates are never looked up in another index.
+pub fn index(
+    config: &Config,
+    id: &str,
+    installed: &InstalledCrateV1,
+) -> Result<IndexConfig, PrebuiltError> {
+    config
+        .indexes
+        .iter()
+        .chain(config.routes.values())
+        .find(|index| index.index.eq(&installed.index))
+        .cloned()
+        .ok_or_else(|| {
+            PrebuiltError::Config(format!(
+                "{id} was installed from index {}, which is not configured.",
+                installed.index
+            ))
+        })
+}
+
+/// Config routing each installed crate to the index it was installed from.
+pub fn routed(config: &Config, state: &State) -> Config {
+    let mut config = config.clone();
+    for (id, installed) in state.crates() {
+        if let Ok(index) = index(&config, id, installed) {
+            config.routes.insert(id.clone(), index);
+        }
+    }
+    config
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinStatus {
+    Ok,
+    Modified,
+    Missing,
+}
+impl From<BinStatus> for &str {
+    fn from(value: BinStatus) -> Self {
+        match value {
+            BinStatus::Ok => "ok",
+            BinStatus::Modified => "modified",
+            BinStatus::Missing => "missing",
+        }
+    }
+}
+
+/// Compare an installed binary against the hashes recorded when it was installed.
+pub fn bin_status(bin: &InstalledBinV1) -> BinStatus {
+    let Ok(bytes) = fs::read(&bin.path)
+    else {
+        return BinStatus::Missing;
+    };
+
+    if bin
+        .hashes
+        .iter()
+        .all(|(hash_type, hash)| hash_type.digest(&bytes).eq(hash))
+    {
+        BinStatus::Ok
+    }
+    else {
+        BinStatus::Modified
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs};
+
+    use super::{bin_status, BinStatus, State};
+    use crate::{
+        config::test_config,
+        data::{HashType, InstalledBinV1, InstalledCrateV1},
+    };
+
+    #[test]
+    fn test_bin_status() {
+        let path = std::env::temp_dir().join(format!("prebuilt-state-{}", std::process::id()));
+        fs::write(&path, b"binary").unwrap();
+        let bin = InstalledBinV1 {
+            path: path.clone(),
+            hashes: HashMap::from([(HashType::Sha256, HashType::Sha256.digest(b"binary"))]),
+        };
+        assert_eq!(bin_status(&bin), BinStatus::Ok);
+
+        fs::write(&path, b"changed").unwrap();
+        assert_eq!(bin_status(&bin), BinStatus::Modified);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bin_status(&bin), BinStatus::Missing);
+    }
+
+    #[test]
+    fn test_state_save() {
+        let root = std::env::temp_dir().join(format!("prebuilt-state-save-{}", std::process::id()));
+        let config = test_config(&root, &["demo"]);
+
+        // Nothing installed yet
+        let mut state = State::load(&config).unwrap();
+        assert!(state.crates().is_empty());
+
+        state.insert(
+            "demo",
+            InstalledCrateV1 {
+                version: "1.0.0".to_string(),
+                index: "file:index".to_string(),
+                target: config.target.clone(),
+                installed: 0,
+                bins: Vec::new(),
+                previous: None,
+            },
+        );
+        state.pin("demo", "1.0.0");
+        state.save().unwrap();
+
+        // Saved in place, without leaving the temp file behind
+        let state = State::load(&config).unwrap();
+        assert_eq!(state.crates()["demo"].version, "1.0.0");
+        assert_eq!(state.pinned(&config, "demo"), Some("1.0.0"));
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+
+        // A malformed state file is not read as empty
+        fs::write(&config.state_path, b"{").unwrap();
+        assert!(State::load(&config).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
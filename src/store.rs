@@ -0,0 +1,264 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache,
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    data::{InstalledBinV1, InstalledCrateV1},
+    error::PrebuiltError,
+    stage::Stage,
+    state::{self, BinStatus},
+};
+
+/// Where an installed binary is kept, named by its hash so a kept file is never changed once linked to.
+///
+/// ```text
+/// $STORE/$ID/$HASH_TYPE-$HASH/$BIN
+/// ```
+pub fn file(config: &Config, id: &str, bin: &InstalledBinV1) -> Result<PathBuf, PrebuiltError> {
+    // Ids come from the index, so do not let them escape the store.
+    if id.is_empty() || id.eq("..") || id.contains(std::path::is_separator) {
+        return Err(PrebuiltError::Malformed(format!(
+            "Illegal crate id {id:?}."
+        )));
+    }
+
+    let Some((hash_type, hash)) = cache::key(&bin.hashes)
+    else {
+        return Err(PrebuiltError::Malformed(format!(
+            "No usable hash for {:?} of {id}.",
+            bin.path
+        )));
+    };
+    let Some(name) = bin.path.file_name()
+    else {
+        return Err(PrebuiltError::Malformed(format!(
+            "No file name for {:?} of {id}.",
+            bin.path
+        )));
+    };
+
+    let mut file = config.store_path.clone();
+    file.push(id);
+    file.push(cache::file_name(hash_type, hash));
+    file.push(name);
+    Ok(file)
+}
+
+/// If a kept binary is there, and unchanged.
+fn stored(file: &Path, bin: &InstalledBinV1) -> BinStatus {
+    state::bin_status(&InstalledBinV1 {
+        path: file.to_path_buf(),
+        hashes: bin.hashes.clone(),
+    })
+}
+
+/// Write extracted binaries into the store, and stage links to them in path.
+pub fn link(
+    config: &Config,
+    stage: &mut Stage,
+    id: &str,
+    bins: &[(InstalledBinV1, Vec<u8>)],
+) -> Result<(), PrebuiltError> {
+    let mut files = Stage::default();
+    let mut links = Vec::new();
+    for (bin, bytes) in bins {
+        let file = file(config, id, bin)?;
+
+        // The same binary is already kept, so links to it stay as they are.
+        if stored(&file, bin) != BinStatus::Ok {
+            if let Some(dir) = file.parent() {
+                create_dir_all(dir).map_err(|_| {
+                    PrebuiltError::Io(format!("Could not create the directory '{dir:?}'."))
+                })?;
+            }
+            files.add(file.clone(), bytes)?;
+        }
+        links.push((bin.path.clone(), file));
+    }
+    files.commit()?;
+
+    for (path, file) in links {
+        // Symlinks are relative to where they are, so point at an absolute path.
+        let file = fs::canonicalize(&file)
+            .map_err(|_| PrebuiltError::Io(format!("Could not find {file:?}.")))?;
+        stage.link(path, &file)?;
+    }
+
+    Ok(())
+}
+
+/// Copy the binaries of an installed version into the store, returns false if they changed since install.
+pub fn keep(
+    config: &Config,
+    id: &str,
+    installed: &InstalledCrateV1,
+) -> Result<bool, PrebuiltError> {
+    let version = &installed.version;
+
+    // Changed binaries are not trusted to be rolled back to.
+    if installed
+        .bins
+        .iter()
+        .any(|bin| state::bin_status(bin) != BinStatus::Ok)
+    {
+        eprintln!(
+            "{} {id}@{version}, its binaries were changed or removed since they were installed.",
+            err_color_print("Could not keep", PossibleColor::BrightYellow)
+        );
+        return Ok(false);
+    }
+
+    for bin in installed.bins.iter() {
+        let to = file(config, id, bin)?;
+
+        // Already kept, or installed with --store and linked to. (Copying onto itself would empty it)
+        if stored(&to, bin) == BinStatus::Ok {
+            continue;
+        }
+
+        if let Some(dir) = to.parent() {
+            create_dir_all(dir).map_err(|_| {
+                PrebuiltError::Io(format!("Could not create the directory '{dir:?}'."))
+            })?;
+        }
+        fs::copy(&bin.path, &to)
+            .map_err(|_| PrebuiltError::Io(format!("Could not copy {:?} to {to:?}.", bin.path)))?;
+    }
+
+    eprintln!(
+        "{} {id}@{version} in {:?}.",
+        err_color_print("Kept", PossibleColor::BrightBlue),
+        config.store_path
+    );
+
+    Ok(true)
+}
+
+/// Remove kept binaries that neither the installed version nor the one it replaced use.
+/// State only records one previous version, so anything older could never be rolled back to.
+pub fn prune(config: &Config, id: &str, installed: &InstalledCrateV1) {
+    let used: Vec<PathBuf> = installed
+        .bins
+        .iter()
+        .chain(installed.previous.iter().flat_map(|p| p.bins.iter()))
+        .filter_map(|bin| file(config, id, bin).ok())
+        .collect();
+
+    let Ok(dirs) = fs::read_dir(config.store_path.join(id))
+    else {
+        return;
+    };
+    for dir in dirs.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let Ok(files) = fs::read_dir(&dir)
+        else {
+            continue;
+        };
+        for file in files.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !used.contains(&file) && fs::remove_file(&file).is_err() {
+                eprintln!("Could not remove kept binary {file:?}.");
+            }
+        }
+        // Only removed once empty.
+        let _ = fs::remove_dir(&dir);
+    }
+}
+
+/// If every binary of an installed version is in the store.
+pub fn contains(config: &Config, id: &str, installed: &InstalledCrateV1) -> bool {
+    installed
+        .bins
+        .iter()
+        .all(|bin| file(config, id, bin).is_ok_and(|f| f.exists()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs};
+
+    use super::{contains, file, keep, link, prune};
+    use crate::{
+        config::test_config,
+        data::{HashType, InstalledBinV1, InstalledCrateV1},
+        stage::Stage,
+    };
+
+    #[test]
+    fn test_keep() {
+        let root = std::env::temp_dir().join(format!("prebuilt-store-{}", std::process::id()));
+        let config = test_config(&root, &["demo"]);
+        fs::create_dir_all(&config.path).unwrap();
+        let path = config.path.join("demo");
+        let bin = |bytes: &[u8]| InstalledBinV1 {
+            path: path.clone(),
+            hashes: HashMap::from([(HashType::Sha256, HashType::Sha256.digest(bytes))]),
+        };
+        fs::write(&path, b"v1").unwrap();
+        let installed = InstalledCrateV1 {
+            version: "1.0.0".to_string(),
+            index: "file:index".to_string(),
+            target: config.target.clone(),
+            installed: 0,
+            bins: vec![bin(b"v1")],
+            previous: None,
+        };
+
+        // Kept binaries are found again
+        assert!(!contains(&config, "demo", &installed));
+        assert!(keep(&config, "demo", &installed).unwrap());
+        assert!(contains(&config, "demo", &installed));
+        let kept = file(&config, "demo", &bin(b"v1")).unwrap();
+        assert_eq!(fs::read(&kept).unwrap(), b"v1");
+
+        // Keeping again leaves the store as is
+        assert!(keep(&config, "demo", &installed).unwrap());
+        assert_eq!(fs::read(&kept).unwrap(), b"v1");
+
+        // Changed binaries are not kept
+        fs::write(&path, b"changed").unwrap();
+        let changed = InstalledCrateV1 {
+            version: "1.1.0".to_string(),
+            ..installed.clone()
+        };
+        assert!(!keep(&config, "demo", &changed).unwrap());
+
+        // Other binaries are written beside kept ones, which are never rewritten
+        let mut stage = Stage::default();
+        link(&config, &mut stage, "demo", &[(bin(b"v2"), b"v2".to_vec())]).unwrap();
+        drop(stage);
+        assert_eq!(fs::read(&kept).unwrap(), b"v1");
+        assert_eq!(
+            fs::read(file(&config, "demo", &bin(b"v2")).unwrap()).unwrap(),
+            b"v2"
+        );
+
+        // Only binaries of the installed and previous versions are left
+        let upgraded = InstalledCrateV1 {
+            version: "1.2.0".to_string(),
+            bins: vec![bin(b"v2")],
+            previous: Some(Box::new(installed.clone())),
+            ..installed.clone()
+        };
+        let old = file(&config, "demo", &bin(b"v0")).unwrap();
+        fs::create_dir_all(old.parent().unwrap()).unwrap();
+        fs::write(&old, b"v0").unwrap();
+        prune(&config, "demo", &upgraded);
+        assert!(!old.exists());
+        assert!(!old.parent().unwrap().exists());
+        assert!(contains(&config, "demo", &upgraded));
+        assert!(contains(&config, "demo", &installed));
+
+        // Names from the index cannot escape the store
+        assert!(file(&config, "..", &bin(b"v1")).is_err());
+        let escape = InstalledBinV1 {
+            path: path.clone(),
+            hashes: HashMap::from([(HashType::Sha256, "../..".to_string())]),
+        };
+        assert!(file(&config, "demo", &escape).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
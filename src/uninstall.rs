@@ -0,0 +1,169 @@
+use std::fs;
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    data::InstalledCrateV1,
+    error::PrebuiltError,
+    events,
+    state::{self, BinStatus, State},
+};
+
+/// Remove exactly the binaries that were installed for each crate, and their reports.
+pub fn uninstall(config: &Config, force: bool) -> Result<(), PrebuiltError> {
+    let mut state = State::load(config)?;
+
+    for pkg in config.pkgs.iter() {
+        let (id, version) = crate::split_pkg(pkg);
+
+        let installed = match state.crates().get(id) {
+            Some(installed) if version.map_or(true, |v| v.eq(&installed.version)) => {
+                installed.clone()
+            }
+            _ => {
+                return Err(events::error(
+                    id,
+                    version.unwrap_or_default(),
+                    config,
+                    PrebuiltError::NotFound(format!("{pkg} is not installed by cargo-prebuilt.")),
+                ))
+            }
+        };
+        uninstall_crate(config, &mut state, id, &installed, force)
+            .map_err(|err| events::error(id, &installed.version, config, err))?;
+    }
+
+    Ok(())
+}
+
+fn uninstall_crate(
+    config: &Config,
+    state: &mut State,
+    id: &str,
+    installed: &InstalledCrateV1,
+    force: bool,
+) -> Result<(), PrebuiltError> {
+    let version = &installed.version;
+
+    // Check every binary first, so a crate is never partly removed.
+    let mut remove = Vec::new();
+    for bin in installed.bins.iter() {
+        match state::bin_status(bin) {
+            BinStatus::Ok => remove.push(&bin.path),
+            BinStatus::Modified if force => remove.push(&bin.path),
+            BinStatus::Modified => {
+                return Err(PrebuiltError::HashMismatch(format!(
+                    "Binary {:?} for {id}@{version} was changed since it was installed. (Use --force to remove it anyway)",
+                    bin.path
+                )))
+            }
+            BinStatus::Missing => eprintln!(
+                "Binary {:?} for {id}@{version} was {}, skipping it.",
+                bin.path,
+                err_color_print("already removed", PossibleColor::BrightYellow)
+            ),
+        }
+    }
+
+    for path in remove {
+        fs::remove_file(path)
+            .map_err(|_| PrebuiltError::Io(format!("Could not remove {path:?}.")))?;
+        eprintln!(
+            "{} {path:?}.",
+            err_color_print("Removed", PossibleColor::BrightPurple)
+        );
+    }
+
+    // Reports, the crate directory is only removed if no other versions have reports.
+    let mut reports = config.report_path.clone();
+    reports.push(id);
+    let crate_reports = reports.clone();
+    reports.push(version);
+    if reports.exists() && fs::remove_dir_all(&reports).is_err() {
+        eprintln!("Could not remove reports {reports:?}.");
+    }
+    let _ = fs::remove_dir(crate_reports);
+
+    // Versions kept for rollback.
+    let store = config.store_path.join(id);
+    if store.exists() && fs::remove_dir_all(&store).is_err() {
+        eprintln!("Could not remove kept versions {store:?}.");
+    }
+
+    state.remove(id);
+    state.save()?;
+
+    eprintln!(
+        "{} {id}@{version}.",
+        err_color_print("Uninstalled", PossibleColor::BrightGreen)
+    );
+    events::uninstalled(id, version, config);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, fs};
+
+    use super::uninstall_crate;
+    use crate::{
+        config::test_config,
+        data::{HashType, InstalledBinV1, InstalledCrateV1},
+        error::PrebuiltError,
+        state::State,
+    };
+
+    #[test]
+    fn test_uninstall() {
+        let root = std::env::temp_dir().join(format!("prebuilt-uninstall-{}", std::process::id()));
+        let config = test_config(&root, &["uninstall", "demo"]);
+        fs::create_dir_all(&config.path).unwrap();
+        let mut state = State::load(&config).unwrap();
+
+        let mut install = |id: &str| {
+            let path = config.path.join(id);
+            fs::write(&path, id).unwrap();
+            fs::create_dir_all(config.store_path.join(id)).unwrap();
+            let installed = InstalledCrateV1 {
+                version: "1.0.0".to_string(),
+                index: "file:index".to_string(),
+                target: config.target.clone(),
+                installed: 0,
+                bins: vec![InstalledBinV1 {
+                    path,
+                    hashes: HashMap::from([(
+                        HashType::Sha256,
+                        HashType::Sha256.digest(id.as_bytes()),
+                    )]),
+                }],
+                previous: None,
+            };
+            state.insert(id, installed.clone());
+            installed
+        };
+        let demo = install("demo");
+        let tool = install("tool");
+
+        // Installed binaries and kept versions are removed
+        uninstall_crate(&config, &mut state, "demo", &demo, false).unwrap();
+        assert!(!config.path.join("demo").exists());
+        assert!(!config.store_path.join("demo").exists());
+        assert!(!state.crates().contains_key("demo"));
+
+        // Changed binaries need --force
+        fs::write(config.path.join("tool"), b"changed").unwrap();
+        assert!(matches!(
+            uninstall_crate(&config, &mut state, "tool", &tool, false),
+            Err(PrebuiltError::HashMismatch(_))
+        ));
+        assert!(config.path.join("tool").exists());
+        assert!(state.crates().contains_key("tool"));
+
+        uninstall_crate(&config, &mut state, "tool", &tool, true).unwrap();
+        assert!(!config.path.join("tool").exists());
+        assert!(State::load(&config).unwrap().crates().is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
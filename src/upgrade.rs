@@ -0,0 +1,311 @@
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    error::PrebuiltError,
+    events,
+    get::Fetcher,
+    state::{self, State},
+};
+
+struct Behind {
+    id: String,
+    version: String, // Installed
+    wanted: String,  // Latest or pinned
+    target: String,  // Installed for
+    pinned: bool,
+}
+
+/// Installed crates that are not on their latest, or pinned, version.
+/// Crates that cannot be checked do not stop the others, the first error is returned with what was found.
+fn behind(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    state: &State,
+    ids: &IndexSet<String>,
+) -> (Vec<Behind>, Option<PrebuiltError>) {
+    let mut behind = Vec::new();
+    let mut failed = None;
+    for id in ids.iter() {
+        match check(config, fetcher, state, id) {
+            Ok(Some(b)) => behind.push(b),
+            Ok(None) => {}
+            Err(err) => keep_first(&mut failed, err),
+        }
+    }
+
+    (behind, failed)
+}
+
+/// Compare an installed crate against the index it was installed from.
+fn check(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    state: &State,
+    id: &str,
+) -> Result<Option<Behind>, PrebuiltError> {
+    let Some(installed) = state.crates().get(id)
+    else {
+        return Err(events::error(
+            id,
+            "",
+            config,
+            PrebuiltError::NotFound(format!("{id} is not installed by cargo-prebuilt.")),
+        ));
+    };
+    let version = &installed.version;
+
+    // A crate with the same name in another index is never an upgrade.
+    state::index(config, id, installed).map_err(|err| events::error(id, version, config, err))?;
+
+    // Held crates never move past their pin.
+    let pin = state.pinned(config, id);
+    let wanted = match pin {
+        Some(pin) => pin.to_string(),
+        None => fetcher
+            .get_latest(id)
+            .map_err(|err| events::error(id, version, config, err))?,
+    };
+
+    if wanted.ne(version) {
+        return Ok(Some(Behind {
+            id: id.to_string(),
+            version: version.clone(),
+            wanted,
+            target: installed.target.clone(),
+            pinned: pin.is_some(),
+        }));
+    }
+    else if pin.is_some() {
+        eprintln!(
+            "{id}@{version} is {}.",
+            err_color_print("pinned", PossibleColor::BrightYellow)
+        );
+    }
+    else {
+        eprintln!(
+            "{id}@{version} is {}.",
+            err_color_print("up to date", PossibleColor::Green)
+        );
+    }
+
+    Ok(None)
+}
+
+/// Keep the first error to return once every crate was tried, later ones are only printed.
+fn keep_first(failed: &mut Option<PrebuiltError>, err: PrebuiltError) {
+    match failed {
+        Some(_) => eprintln!(
+            "{} {err}",
+            err_color_print("Error:", PossibleColor::BrightRed)
+        ),
+        None => *failed = Some(err),
+    }
+}
+
+/// Print installed crates that are behind the index they were installed from.
+pub fn outdated(config: &Config, fetcher: &mut Fetcher) -> Result<(), PrebuiltError> {
+    let state = State::load(config)?;
+    let ids = state.crates().keys().cloned().collect();
+
+    let config = &state::routed(config, &state);
+    let fetcher = &mut Fetcher::quiet(config, fetcher.agent())?;
+
+    let (behind, failed) = behind(config, fetcher, &state, &ids);
+    for b in behind {
+        eprintln!(
+            "{} {}@{}, {} is {}.",
+            err_color_print("Outdated", PossibleColor::BrightYellow),
+            b.id,
+            b.version,
+            if b.pinned { "pinned" } else { "latest" },
+            b.wanted
+        );
+        events::outdated(&b.id, &b.version, config, &b.wanted);
+    }
+
+    failed.map_or(Ok(()), Err)
+}
+
+/// Install the latest version of installed crates that are behind, from the index and for the target they were installed with.
+/// Held crates are skipped.
+pub fn upgrade(config: &Config, fetcher: &mut Fetcher) -> Result<(), PrebuiltError> {
+    let state = State::load(config)?;
+
+    // Every installed crate if none were passed
+    let ids = if config.pkgs.is_empty() {
+        state.crates().keys().cloned().collect()
+    }
+    else {
+        config.pkgs.clone()
+    };
+
+    let config = &state::routed(config, &state);
+    let fetcher = &mut Fetcher::quiet(config, fetcher.agent())?;
+
+    let (behind, mut failed) = behind(config, fetcher, &state, &ids);
+    let mut targets: IndexMap<String, IndexSet<String>> = IndexMap::new();
+    for b in behind {
+        // Pins are only reported by outdated, upgrade never moves a held crate.
+        if b.pinned {
+            eprintln!(
+                "{} {}@{}, it is pinned to {}.",
+                err_color_print("Skipping", PossibleColor::BrightYellow),
+                b.id,
+                b.version,
+                b.wanted
+            );
+            continue;
+        }
+
+        eprintln!(
+            "{} {}@{} to {}.",
+            err_color_print("Upgrading", PossibleColor::BrightBlue),
+            b.id,
+            b.version,
+            b.wanted
+        );
+        targets
+            .entry(b.target)
+            .or_default()
+            .insert(format!("{}@{}", b.id, b.wanted));
+    }
+
+    for (target, pkgs) in targets {
+        let config = Config {
+            target,
+            ..config.clone()
+        };
+        if let Err(err) = crate::install(&config, fetcher, &pkgs) {
+            keep_first(&mut failed, err);
+        }
+    }
+
+    failed.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path};
+
+    use super::{outdated, upgrade};
+    use crate::{
+        config::{test_config, Config},
+        create_agent,
+        data::InstalledCrateV1,
+        events,
+        get::Fetcher,
+        install,
+        state::State,
+        test::index_crate,
+    };
+
+    /// Install tool, held, and cross from an internal index, with a public index shadowing tool.
+    /// Returns the index args to use afterwards.
+    fn setup(root: &Path) -> String {
+        let internal = root.join("internal");
+        let public = root.join("public");
+        let other = "other-target";
+
+        let config = test_config(root, &["demo"]);
+        let target = config.target.as_str();
+        for (id, target) in [("tool", target), ("held", target), ("cross", other)] {
+            index_crate(
+                &internal,
+                target,
+                id,
+                "1.0.0",
+                format!("{id} 1").as_bytes(),
+                None,
+            );
+            index_crate(
+                &internal,
+                target,
+                id,
+                "1.1.0",
+                format!("{id} 2").as_bytes(),
+                None,
+            );
+        }
+        index_crate(&public, target, "tool", "9.9.9", b"shadowed", None);
+
+        let index = format!("--index=file:{}", internal.display());
+        let install_with = |args: &[&str]| {
+            let config = test_config(root, args);
+            let mut fetcher = Fetcher::new(&config, create_agent().unwrap()).unwrap();
+            install(&config, &mut fetcher, &config.pkgs).unwrap();
+        };
+        install_with(&[&index, "tool@1.0.0,held@1.0.0"]);
+        install_with(&[&index, &format!("--target={other}"), "cross@1.0.0"]);
+
+        // Held at a newer version, and a crate the index no longer has
+        let mut state = State::load(&config).unwrap();
+        state.pin("held", "1.1.0");
+        let gone = InstalledCrateV1 {
+            version: "1.0.0".to_string(),
+            bins: Vec::new(),
+            ..state.crates()["tool"].clone()
+        };
+        state.insert("gone", gone);
+        state.save().unwrap();
+
+        format!(
+            "--index=file:{},file:{}",
+            public.display(),
+            internal.display()
+        )
+    }
+
+    fn fetcher(config: &Config) -> Fetcher {
+        Fetcher::new(config, create_agent().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_outdated() {
+        let root = std::env::temp_dir().join(format!("prebuilt-outdated-{}", std::process::id()));
+        let index = setup(&root);
+        let config = test_config(&root, &[&index, "--out", "outdated"]);
+
+        // Checked against the index each crate came from, past one that is gone
+        let (res, held) = events::hold(|| outdated(&config, &mut fetcher(&config)));
+        assert!(res.is_err());
+        let outdated: Vec<&String> = held.iter().filter(|e| e.contains("\"outdated\"")).collect();
+        assert_eq!(outdated.len(), 3);
+        assert!(outdated.iter().all(|e| e.contains("\"1.1.0\"")));
+        assert!(held
+            .iter()
+            .any(|e| e.contains("\"error\"") && e.contains("\"gone\"")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let root = std::env::temp_dir().join(format!("prebuilt-upgrade-{}", std::process::id()));
+        let index = setup(&root);
+        let config = test_config(&root, &[&index, "upgrade"]);
+
+        // The crate that is gone fails, but the rest are still upgraded
+        assert!(upgrade(&config, &mut fetcher(&config)).is_err());
+        let state = State::load(&config).unwrap();
+
+        // Never from the public index shadowing it
+        let tool = &state.crates()["tool"];
+        assert_eq!(tool.version, "1.1.0");
+        assert!(tool.index.ends_with("internal"));
+        assert_eq!(fs::read(config.path.join("tool")).unwrap(), b"tool 2");
+
+        // For the target it was installed for
+        let cross = &state.crates()["cross"];
+        assert_eq!(cross.version, "1.1.0");
+        assert_eq!(cross.target, "other-target");
+        assert_eq!(fs::read(config.path.join("cross")).unwrap(), b"cross 2");
+
+        // Held crates are skipped
+        assert_eq!(state.crates()["held"].version, "1.0.0");
+        assert_eq!(fs::read(config.path.join("held")).unwrap(), b"held 1");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
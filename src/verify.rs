@@ -0,0 +1,179 @@
+use std::{fs, path::Path};
+
+use crate::{
+    color::{err_color_print, PossibleColor},
+    config::Config,
+    data::{HashesFileImm, InstalledCrateV1},
+    error::PrebuiltError,
+    events,
+    get::Fetcher,
+    state::{self, State},
+};
+
+/// Re-hash installed binaries against the index, to catch tampered or drifted binaries.
+pub fn verify(config: &Config, fetcher: &mut Fetcher) -> Result<(), PrebuiltError> {
+    let state = State::load(config)?;
+
+    // Recorded crates are only checked against the index they were installed from.
+    let config = &state::routed(config, &state);
+    let fetcher = &mut Fetcher::quiet(config, fetcher.agent())?;
+
+    for pkg in config.pkgs.iter() {
+        // Without a version the installed one is used, or else the latest one is pulled.
+        let (id, version) = crate::split_pkg(pkg);
+
+        // Recorded crates are checked at the version, target, and paths they were installed with.
+        let installed = state
+            .crates()
+            .get(id)
+            .filter(|installed| version.map_or(true, |v| v.eq(&installed.version)));
+        if let Some(installed) = installed {
+            verify_installed(config, fetcher, id, installed)
+                .map_err(|err| events::error(id, &installed.version, config, err))?;
+            continue;
+        }
+
+        let version = match version {
+            Some(v) => v.to_string(),
+            None => fetcher
+                .get_latest(id)
+                .map_err(|err| events::error(id, "", config, err))?,
+        };
+
+        verify_crate(config, fetcher, id, &version)
+            .map_err(|err| events::error(id, &version, config, err))?;
+    }
+
+    Ok(())
+}
+
+fn verify_installed(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    id: &str,
+    installed: &InstalledCrateV1,
+) -> Result<(), PrebuiltError> {
+    let version = &installed.version;
+    state::index(config, id, installed)?;
+    let config = &Config {
+        target: installed.target.clone(),
+        ..config.clone()
+    };
+    let (_, hashes) = fetcher.hashes(id, version, config)?;
+
+    for bin in installed.bins.iter() {
+        let bytes = fs::read(&bin.path).map_err(|_| {
+            PrebuiltError::NotFound(format!(
+                "Binary {:?} for {id}@{version} is missing.",
+                bin.path
+            ))
+        })?;
+        verify_path(config, fetcher, id, version, &hashes, &bin.path, &bytes)?;
+    }
+
+    eprintln!(
+        "{} {id}@{version}.",
+        err_color_print("Verified", PossibleColor::BrightGreen)
+    );
+
+    Ok(())
+}
+
+fn verify_crate(
+    config: &Config,
+    fetcher: &mut Fetcher,
+    id: &str,
+    version: &str,
+) -> Result<(), PrebuiltError> {
+    let (info, hashes) = fetcher.hashes(id, version, config)?;
+
+    for bin in info.bins.iter() {
+        // Windows binaries are installed with their extension.
+        let mut path = config.path.join(bin);
+        if !path.exists() {
+            path = config.path.join(format!("{bin}.exe"));
+        }
+
+        let bytes = fs::read(&path).map_err(|_| {
+            PrebuiltError::NotFound(format!(
+                "Binary {bin} for {id}@{version} is not installed in {:?}.",
+                config.path
+            ))
+        })?;
+        verify_path(config, fetcher, id, version, &hashes, &path, &bytes)?;
+    }
+
+    eprintln!(
+        "{} {id}@{version}.",
+        err_color_print("Verified", PossibleColor::BrightGreen)
+    );
+
+    Ok(())
+}
+
+fn verify_path(
+    config: &Config,
+    fetcher: &Fetcher,
+    id: &str,
+    version: &str,
+    hashes: &HashesFileImm,
+    path: &Path,
+    bytes: &[u8],
+) -> Result<(), PrebuiltError> {
+    let bin_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    fetcher.verify_bin(id, version, config, hashes, bin_name, bytes)?;
+    events::binary_verified(id, version, config, path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::verify;
+    use crate::{
+        config::test_config, create_agent, error::PrebuiltError, get::Fetcher, install,
+        test::index_crate,
+    };
+
+    #[test]
+    fn test_verify() {
+        let root = std::env::temp_dir().join(format!("prebuilt-verify-{}", std::process::id()));
+        let (public, internal) = (root.join("public"), root.join("internal"));
+        let internal_arg = format!("--index=file:{}", internal.display());
+        let config = test_config(&root, &[&internal_arg, "tool"]);
+        index_crate(&internal, &config.target, "tool", "1.0.0", b"tool", None);
+        index_crate(&public, &config.target, "tool", "1.0.0", b"shadow", None);
+        let mut fetcher = Fetcher::new(&config, create_agent().unwrap()).unwrap();
+        install(&config, &mut fetcher, &config.pkgs).unwrap();
+
+        let run = |index: &str| {
+            let config = test_config(&root, &[index, "verify", "tool"]);
+            verify(
+                &config,
+                &mut Fetcher::new(&config, create_agent().unwrap()).unwrap(),
+            )
+        };
+        let both = format!(
+            "--index=file:{},file:{}",
+            public.display(),
+            internal.display()
+        );
+
+        // Checked against the index it was installed from, even behind another one
+        run(&both).unwrap();
+
+        // Which has to be configured
+        let public_arg = format!("--index=file:{}", public.display());
+        assert!(matches!(run(&public_arg), Err(PrebuiltError::Config(_))));
+
+        // Changed binaries fail
+        fs::write(config.path.join("tool"), b"changed").unwrap();
+        assert!(matches!(run(&both), Err(PrebuiltError::HashMismatch(_))));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}